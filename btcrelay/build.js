@@ -12,6 +12,16 @@ const ENV_TO_KEY = {
   mainnet: "default",
   testnet3: "default",
   testnet4: "testnet4",
+  regtest: "default",
+  signet: "signet",
+};
+
+const ENV_TO_FEATURES = {
+  mainnet: [],
+  testnet3: ["bitcoin_testnet"],
+  testnet4: ["bitcoin_testnet4"],
+  regtest: ["bitcoin_regtest"],
+  signet: ["bitcoin_signet"],
 };
 
 const CLUSTERS = ["mainnet", "localnet", "devnet"];
@@ -33,6 +43,7 @@ const targetKeypairPath = path.join(targetDeployDir, `${PROGRAM_NAME}-keypair.js
 const keyPaths = {
   default: path.join(keysDir, "default.json"),
   testnet4: path.join(keysDir, "testnet4.json"),
+  signet: path.join(keysDir, "signet.json"),
 };
 
 function ensureDir(dir) {
@@ -125,6 +136,7 @@ function main() {
 
   generateKeypairIfMissing(keyPaths.default);
   generateKeypairIfMissing(keyPaths.testnet4);
+  generateKeypairIfMissing(keyPaths.signet);
 
   const selectedKeyName = ENV_TO_KEY[envName];
   const selectedKeyPath = keyPaths[selectedKeyName];
@@ -146,7 +158,8 @@ function main() {
 
   updateAnchorToml(selectedProgramId);
 
-  run("anchor", ["build"].concat(envName==="mainnet" ? [] : ["--", "--features", "bitcoin_testnet"]));
+  const features = ENV_TO_FEATURES[envName];
+  run("anchor", ["build"].concat(features.length===0 ? [] : ["--", "--features", features.join(",")]));
 }
 
 try {
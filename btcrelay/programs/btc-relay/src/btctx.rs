@@ -0,0 +1,292 @@
+//Utilities for parsing bitcoin transactions
+use anchor_lang::solana_program::hash;
+
+//Script opcodes
+pub const OP_0: u8 = 0x00;
+pub const OP_PUSHDATA1: u8 = 0x4c;
+pub const OP_PUSHDATA2: u8 = 0x4d;
+pub const OP_PUSHDATA4: u8 = 0x4e;
+pub const OP_1: u8 = 0x51;
+pub const OP_16: u8 = 0x60;
+pub const OP_RETURN: u8 = 0x6a;
+pub const OP_CHECKMULTISIG: u8 = 0xae;
+
+//Coinbase output script prefix of the segwit witness commitment - OP_RETURN PUSH36 0xaa21a9ed
+const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+const MINIMUM_WITNESS_COMMITMENT: usize = 38;
+
+//Reads a varint from the data at a start index, returns the value and the size of the varint,
+// or None if the data is too short
+//varint description: https://en.bitcoin.it/wiki/Protocol_documentation#Variable_length_integer
+pub fn read_var_int(data: &[u8], start: usize) -> Option<(u64, usize)> {
+    let first = *data.get(start)?;
+    if first <= 0xFC {
+        Some((first as u64, 1))
+    } else if first == 0xFD {
+        let val = u16::from_le_bytes(read_slice(data, start+1, 2)?.try_into().unwrap());
+        Some((val as u64, 3))
+    } else if first == 0xFE {
+        let val = u32::from_le_bytes(read_slice(data, start+1, 4)?.try_into().unwrap());
+        Some((val as u64, 5))
+    } else {
+        let val = u64::from_le_bytes(read_slice(data, start+1, 8)?.try_into().unwrap());
+        Some((val, 9))
+    }
+}
+
+//Writes a varint to the end of the data vector
+pub fn write_var_int(value: u64, data: &mut Vec<u8>) {
+    if value <= 0xFC {
+        data.push(value as u8);
+    } else if value <= 0xFFFF {
+        data.push(0xFD);
+        data.extend_from_slice(&(value as u16).to_le_bytes());
+    } else if value <= 0xFFFFFFFF {
+        data.push(0xFE);
+        data.extend_from_slice(&(value as u32).to_le_bytes());
+    } else {
+        data.push(0xFF);
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+//Returns a slice of the data of specified length at a start index, or None if the data is too short
+pub fn read_slice(data: &[u8], start: usize, len: usize) -> Option<&[u8]> {
+    data.get(start..start.checked_add(len)?)
+}
+
+//Reads a varint length prefixed byte array from the data at a start index,
+// returns the byte array and the total size (including the varint)
+pub fn read_var_slice(data: &[u8], start: usize) -> Option<(&[u8], usize)> {
+    let (len, varint_len) = read_var_int(data, start)?;
+    let slice = read_slice(data, start+varint_len, usize::try_from(len).ok()?)?;
+    Some((slice, varint_len+slice.len()))
+}
+
+pub struct TxInput<'a> {
+    pub reversed_prev_txid: [u8; 32], //Transaction ID of the spent UTXO
+    pub prev_vout: u32, //Output index of the spent UTXO
+    pub script: &'a [u8] //Input script (scriptSig)
+}
+
+pub struct TxOutput<'a> {
+    pub value: u64, //Output value in satoshis
+    pub script: &'a [u8], //Output script (scriptPubKey)
+    pub offset: usize //Offset of the output in the transaction data
+}
+
+pub struct BitcoinTransaction<'a> {
+    pub inputs: Vec<TxInput<'a>>,
+    pub outputs: Vec<TxOutput<'a>>
+}
+
+//Parses a bitcoin transaction, only works for non-segwit transactions, so segwit data
+// has to be stripped out from the tx off-chain, returns None if the data is malformed
+//Format description: https://en.bitcoin.it/wiki/Transaction
+pub fn parse_transaction(data: &[u8]) -> Option<BitcoinTransaction<'_>> {
+    let mut offset = 4; //Version
+
+    //Transaction without inputs is invalid, this also rejects the segwit marker (0x00)
+    let (input_count, varint_len) = read_var_int(data, offset)?;
+    if input_count==0 {
+        return None;
+    }
+    offset += varint_len;
+
+    let mut inputs = Vec::new();
+    for _i in 0..input_count {
+        let reversed_prev_txid: [u8; 32] = read_slice(data, offset, 32)?.try_into().unwrap();
+        offset += 32; //UTXO
+        let prev_vout = u32::from_le_bytes(read_slice(data, offset, 4)?.try_into().unwrap());
+        offset += 4; //Index
+        let (script, total_len) = read_var_slice(data, offset)?;
+        offset += total_len; //Script len + script
+        offset += 4; //Sequence
+        inputs.push(TxInput {
+            reversed_prev_txid,
+            prev_vout,
+            script
+        });
+    }
+
+    let (output_count, varint_len) = read_var_int(data, offset)?;
+    offset += varint_len;
+
+    let mut outputs = Vec::new();
+    for _i in 0..output_count {
+        let output_offset = offset;
+        let value = u64::from_le_bytes(read_slice(data, offset, 8)?.try_into().unwrap());
+        offset += 8; //Value
+        let (script, total_len) = read_var_slice(data, offset)?;
+        offset += total_len; //Script len + script
+        outputs.push(TxOutput {
+            value,
+            script,
+            offset: output_offset
+        });
+    }
+
+    offset += 4; //Locktime
+
+    //Has to consume all the data
+    if offset!=data.len() {
+        return None;
+    }
+
+    Some(BitcoinTransaction {
+        inputs,
+        outputs
+    })
+}
+
+pub struct WitnessTransaction<'a> {
+    pub stripped: Vec<u8>, //Transaction data with the witness data stripped, used to compute the transaction ID
+    pub witnesses: Vec<Vec<&'a [u8]>> //Witness stack of every input
+}
+
+//Parses a segwit transaction (BIP144 serialization with marker & flag), extracting the witness stacks of the inputs
+// and the transaction data with the witness data stripped, returns None if the data is malformed or doesn't contain
+// any witness data
+//Format description: https://github.com/bitcoin/bips/blob/master/bip-0144.mediawiki#serialization
+pub fn parse_witness_transaction(data: &[u8]) -> Option<WitnessTransaction<'_>> {
+    //Marker & flag
+    if read_slice(data, 4, 2)? != [0x00, 0x01] {
+        return None;
+    }
+    let mut offset = 6;
+
+    let (input_count, varint_len) = read_var_int(data, offset)?;
+    offset += varint_len;
+    for _i in 0..input_count {
+        offset += 36; //UTXO + index
+        let (_, total_len) = read_var_slice(data, offset)?;
+        offset += total_len; //Script len + script
+        offset += 4; //Sequence
+    }
+
+    let (output_count, varint_len) = read_var_int(data, offset)?;
+    offset += varint_len;
+    for _i in 0..output_count {
+        offset += 8; //Value
+        let (_, total_len) = read_var_slice(data, offset)?;
+        offset += total_len; //Script len + script
+    }
+    let witness_start = offset;
+
+    let mut witnesses = Vec::new();
+    for _i in 0..input_count {
+        let (item_count, varint_len) = read_var_int(data, offset)?;
+        offset += varint_len;
+        let mut witness = Vec::new();
+        for _j in 0..item_count {
+            let (item, total_len) = read_var_slice(data, offset)?;
+            offset += total_len;
+            witness.push(item);
+        }
+        witnesses.push(witness);
+    }
+
+    //Witness data has to be present, bitcoin core rejects segwit serialization with all the witnesses empty
+    if witnesses.iter().all(|witness| witness.is_empty()) {
+        return None;
+    }
+
+    let locktime = read_slice(data, offset, 4)?;
+    offset += 4;
+    if offset!=data.len() {
+        return None;
+    }
+
+    let mut stripped = Vec::with_capacity(witness_start + 2);
+    stripped.extend_from_slice(&data[0..4]);
+    stripped.extend_from_slice(&data[6..witness_start]);
+    stripped.extend_from_slice(locktime);
+
+    Some(WitnessTransaction {
+        stripped,
+        witnesses
+    })
+}
+
+//Double sha256 of the transaction data, this is the reversed transaction ID,
+// reversed_ prefix is used because bitcoin uses little endian encoding
+pub fn get_reversed_txid(data: &[u8]) -> [u8; 32] {
+    hash::hash(&hash::hash(data).to_bytes()).to_bytes()
+}
+
+//Double sha256 of the full segwit transaction data (including witness data), this is the reversed witness transaction ID
+pub fn get_reversed_wtxid(data: &[u8]) -> [u8; 32] {
+    get_reversed_txid(data)
+}
+
+//Reads a single script operation from the script at a start index, returns the opcode,
+// data pushed by the operation (empty for non-push opcodes) and the total size of the operation
+//Script description: https://en.bitcoin.it/wiki/Script
+pub fn read_script_op(script: &[u8], start: usize) -> Option<(u8, &[u8], usize)> {
+    let opcode = *script.get(start)?;
+    let (len, len_size) = match opcode {
+        0x01..=0x4b => (opcode as usize, 0),
+        OP_PUSHDATA1 => (*script.get(start+1)? as usize, 1),
+        OP_PUSHDATA2 => (u16::from_le_bytes(read_slice(script, start+1, 2)?.try_into().unwrap()) as usize, 2),
+        OP_PUSHDATA4 => (usize::try_from(u32::from_le_bytes(read_slice(script, start+1, 4)?.try_into().unwrap())).ok()?, 4),
+        _ => (0, 0)
+    };
+    let data = read_slice(script, start+1+len_size, len)?;
+    Some((opcode, data, 1+len_size+len))
+}
+
+//Writes a minimally encoded data push to the end of the script
+pub fn write_script_push(data: &[u8], script: &mut Vec<u8>) {
+    if data.len() < OP_PUSHDATA1 as usize {
+        script.push(data.len() as u8);
+    } else if data.len() <= 0xFF {
+        script.push(OP_PUSHDATA1);
+        script.push(data.len() as u8);
+    } else if data.len() <= 0xFFFF {
+        script.push(OP_PUSHDATA2);
+        script.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    } else {
+        script.push(OP_PUSHDATA4);
+        script.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    }
+    script.extend_from_slice(data);
+}
+
+//Writes a number push to the end of the script, same as bitcoin core's CScript << operator,
+// small numbers are pushed with OP_0 & OP_1..OP_16 opcodes, others as a minimally encoded little endian script number
+pub fn write_script_number(value: u32, script: &mut Vec<u8>) {
+    if value == 0 {
+        script.push(OP_0);
+        return;
+    }
+    if value <= 16 {
+        script.push(OP_1 - 1 + value as u8);
+        return;
+    }
+
+    let mut data = value.to_le_bytes().to_vec();
+    while data.last() == Some(&0) {
+        data.pop();
+    }
+    //Highest bit of the last byte is the sign bit
+    if data.last().unwrap() & 0x80 != 0 {
+        data.push(0x00);
+    }
+    write_script_push(&data, script);
+}
+
+//Returns the index of the output containing the segwit witness commitment, if there are multiple
+// such outputs the last one is used
+//https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki#commitment-structure
+pub fn get_witness_commitment_index(tx: &BitcoinTransaction) -> Option<usize> {
+    tx.outputs.iter().rposition(|output| {
+        output.script.len() >= MINIMUM_WITNESS_COMMITMENT && output.script[0..6] == WITNESS_COMMITMENT_HEADER
+    })
+}
+
+//Returns the segwit witness commitment (double sha256 of the witness merkle root & witness reserved value)
+// from the coinbase transaction, if present
+pub fn get_witness_commitment(tx: &BitcoinTransaction) -> Option<[u8; 32]> {
+    let index = get_witness_commitment_index(tx)?;
+    Some(tx.outputs[index].script[6..38].try_into().unwrap())
+}
@@ -0,0 +1,229 @@
+//Bitcoin network consensus parameters, the network is selected at compile time by a cargo feature:
+// none - mainnet
+// bitcoin_testnet - testnet3
+// bitcoin_testnet4 - testnet4
+// bitcoin_signet - default public signet
+// bitcoin_regtest - regtest
+//https://github.com/bitcoin/bitcoin/blob/master/src/kernel/chainparams.cpp
+use crate::u256::U256;
+
+#[cfg(any(
+    all(feature = "bitcoin_testnet", feature = "bitcoin_testnet4"),
+    all(feature = "bitcoin_testnet", feature = "bitcoin_regtest"),
+    all(feature = "bitcoin_testnet", feature = "bitcoin_signet"),
+    all(feature = "bitcoin_testnet4", feature = "bitcoin_regtest"),
+    all(feature = "bitcoin_testnet4", feature = "bitcoin_signet"),
+    all(feature = "bitcoin_regtest", feature = "bitcoin_signet")
+))]
+compile_error!("Features bitcoin_testnet, bitcoin_testnet4, bitcoin_regtest and bitcoin_signet are mutually exclusive");
+
+pub struct ConsensusParams {
+    pub pow_limit: U256, //Maximum target (lowest possible mining difficulty)
+    pub pow_limit_nbits: u32, //Maximum target compressed to nBits
+    pub max_difficulty: U256, //Numerator used for calculating block's difficulty (work) from its target
+
+    pub diff_adjustment_interval: u32, //Difficulty is retargetted every diff_adjustment_interval blocks
+    pub target_timespan: u32, //Expected duration of the difficulty period
+    pub no_retargeting: bool, //Difficulty is never retargetted
+
+    pub allow_min_difficulty_blocks: bool, //Block can be mined with minimum difficulty if no block was found for min_difficulty_block_spacing
+    pub min_difficulty_block_spacing: u32,
+    pub enforce_bip94: bool, //Retargetting uses the first block of the period & timewarp protection is enforced
+
+    pub max_future_blocktime: Option<u32>, //Maximum positive difference between bitcoin block's timestamp and Solana's on-chain clock, None to disable the check
+
+    pub bip34_height: u32, //Blocks starting from this height have to commit to their height in the coinbase transaction (BIP34)
+
+    pub reversed_genesis_block_hash: [u8; 32], //Checkpoint, blockhash of the genesis block
+    pub signet_challenge: Option<&'static [u8]> //Challenge script for signet block solutions, None for non-signet networks
+}
+
+//BIP94 timewarp protection, the first block of a difficulty period can have timestamp
+// at most MAX_TIMEWARP seconds before the last block of the previous period
+//https://github.com/bitcoin/bips/blob/master/bip-0094.mediawiki
+pub const MAX_TIMEWARP: u32 = 10 * 60;
+
+const DIFF_ADJUSTMENT_INTERVAL: u32 = 2016;
+const TARGET_TIMESPAN: u32 = 14 * 24 * 60 * 60; // 2 weeks
+const MIN_DIFFICULTY_BLOCK_SPACING: u32 = 2 * 10 * 60; // 20 minutes
+
+//Nodes in bitcoin network generally reject any block with timestamp more than 2 hours in the future
+//As we are dealing with another blockchain here,
+// with the possibility of the Solana's on-chain clock being skewed, we chose double the value - 4 hours
+#[cfg(not(feature = "bitcoin_regtest"))]
+const MAX_FUTURE_BLOCKTIME: u32 = 4 * 60 * 60;
+
+//https://en.bitcoin.it/wiki/Target#What_is_the_maximum_target.3F
+#[cfg(not(any(feature = "bitcoin_regtest", feature = "bitcoin_signet")))]
+const POW_LIMIT: U256 = U256::from_be_bytes([
+    0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
+]);
+
+//https://en.bitcoin.it/wiki/Difficulty#How_is_difficulty_calculated.3F_What_is_the_difference_between_bdiff_and_pdiff.3F
+#[cfg(not(any(feature = "bitcoin_regtest", feature = "bitcoin_signet")))]
+const MAX_DIFFICULTY: U256 = U256::from_be_bytes([
+    0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+]);
+
+//Regtest & signet targets are way above the mainnet maximum target, which would make their difficulty
+// round down to 0, so the full 2^256 range is used instead, same as chain work in bitcoin core
+#[cfg(any(feature = "bitcoin_regtest", feature = "bitcoin_signet"))]
+const MAX_DIFFICULTY: U256 = U256::from_be_bytes([0xff; 32]);
+
+#[cfg(not(any(
+    feature = "bitcoin_testnet",
+    feature = "bitcoin_testnet4",
+    feature = "bitcoin_regtest",
+    feature = "bitcoin_signet"
+)))]
+pub const PARAMS: ConsensusParams = ConsensusParams {
+    pow_limit: POW_LIMIT,
+    pow_limit_nbits: 0x1d00ffff,
+    max_difficulty: MAX_DIFFICULTY,
+
+    diff_adjustment_interval: DIFF_ADJUSTMENT_INTERVAL,
+    target_timespan: TARGET_TIMESPAN,
+    no_retargeting: false,
+
+    allow_min_difficulty_blocks: false,
+    min_difficulty_block_spacing: MIN_DIFFICULTY_BLOCK_SPACING,
+    enforce_bip94: false,
+
+    max_future_blocktime: Some(MAX_FUTURE_BLOCKTIME),
+
+    bip34_height: 227931,
+
+    //000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f
+    reversed_genesis_block_hash: [
+        0x6f, 0xe2, 0x8c, 0x0a, 0xb6, 0xf1, 0xb3, 0x72, 0xc1, 0xa6, 0xa2, 0x46, 0xae, 0x63, 0xf7, 0x4f,
+        0x93, 0x1e, 0x83, 0x65, 0xe1, 0x5a, 0x08, 0x9c, 0x68, 0xd6, 0x19, 0x00, 0x00, 0x00, 0x00, 0x00
+    ],
+    signet_challenge: None
+};
+
+//Testnet3 allows blocks with minimum difficulty if no block was found for 20 minutes
+#[cfg(feature = "bitcoin_testnet")]
+pub const PARAMS: ConsensusParams = ConsensusParams {
+    pow_limit: POW_LIMIT,
+    pow_limit_nbits: 0x1d00ffff,
+    max_difficulty: MAX_DIFFICULTY,
+
+    diff_adjustment_interval: DIFF_ADJUSTMENT_INTERVAL,
+    target_timespan: TARGET_TIMESPAN,
+    no_retargeting: false,
+
+    allow_min_difficulty_blocks: true,
+    min_difficulty_block_spacing: MIN_DIFFICULTY_BLOCK_SPACING,
+    enforce_bip94: false,
+
+    max_future_blocktime: Some(MAX_FUTURE_BLOCKTIME),
+
+    bip34_height: 21111,
+
+    //000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943
+    reversed_genesis_block_hash: [
+        0x43, 0x49, 0x7f, 0xd7, 0xf8, 0x26, 0x95, 0x71, 0x08, 0xf4, 0xa3, 0x0f, 0xd9, 0xce, 0xc3, 0xae,
+        0xba, 0x79, 0x97, 0x20, 0x84, 0xe9, 0x0e, 0xad, 0x01, 0xea, 0x33, 0x09, 0x00, 0x00, 0x00, 0x00
+    ],
+    signet_challenge: None
+};
+
+//Testnet4 keeps the testnet3 minimum difficulty rule and adds BIP94 rules on top
+//https://github.com/bitcoin/bips/blob/master/bip-0094.mediawiki
+#[cfg(feature = "bitcoin_testnet4")]
+pub const PARAMS: ConsensusParams = ConsensusParams {
+    pow_limit: POW_LIMIT,
+    pow_limit_nbits: 0x1d00ffff,
+    max_difficulty: MAX_DIFFICULTY,
+
+    diff_adjustment_interval: DIFF_ADJUSTMENT_INTERVAL,
+    target_timespan: TARGET_TIMESPAN,
+    no_retargeting: false,
+
+    allow_min_difficulty_blocks: true,
+    min_difficulty_block_spacing: MIN_DIFFICULTY_BLOCK_SPACING,
+    enforce_bip94: true,
+
+    max_future_blocktime: Some(MAX_FUTURE_BLOCKTIME),
+
+    bip34_height: 1,
+
+    //00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043
+    reversed_genesis_block_hash: [
+        0x43, 0xf0, 0x8b, 0xda, 0xb0, 0x50, 0xe3, 0x5b, 0x56, 0x7c, 0x86, 0x4b, 0x91, 0xf4, 0x7f, 0x50,
+        0xae, 0x72, 0x5a, 0xe2, 0xde, 0x53, 0xbc, 0xfb, 0xba, 0xf2, 0x84, 0xda, 0x00, 0x00, 0x00, 0x00
+    ],
+    signet_challenge: None
+};
+
+//Default public signet, blocks have to be signed by the signet challenge signers (1-of-2 bare multisig)
+//https://github.com/bitcoin/bips/blob/master/bip-0325.mediawiki
+#[cfg(feature = "bitcoin_signet")]
+pub const PARAMS: ConsensusParams = ConsensusParams {
+    pow_limit: U256::from_be_bytes([
+        0x00, 0x00, 0x03, 0x77, 0xae, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    ]),
+    pow_limit_nbits: 0x1e0377ae,
+    max_difficulty: MAX_DIFFICULTY,
+
+    diff_adjustment_interval: DIFF_ADJUSTMENT_INTERVAL,
+    target_timespan: TARGET_TIMESPAN,
+    no_retargeting: false,
+
+    allow_min_difficulty_blocks: false,
+    min_difficulty_block_spacing: MIN_DIFFICULTY_BLOCK_SPACING,
+    enforce_bip94: false,
+
+    max_future_blocktime: Some(MAX_FUTURE_BLOCKTIME),
+
+    bip34_height: 1,
+
+    //00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6
+    reversed_genesis_block_hash: [
+        0xf6, 0x1e, 0xee, 0x3b, 0x63, 0xa3, 0x80, 0xa4, 0x77, 0xa0, 0x63, 0xaf, 0x32, 0xb2, 0xbb, 0xc9,
+        0x7c, 0x9f, 0xf9, 0xf0, 0x1f, 0x2c, 0x42, 0x25, 0xe9, 0x73, 0x98, 0x81, 0x08, 0x00, 0x00, 0x00
+    ],
+    signet_challenge: Some(&[
+        0x51, 0x21, 0x03, 0xad, 0x5e, 0x0e, 0xda, 0xd1, 0x8c, 0xb1, 0xf0, 0xfc, 0x0d, 0x28, 0xa3, 0xd4,
+        0xf1, 0xf3, 0xe4, 0x45, 0x64, 0x03, 0x37, 0x48, 0x9a, 0xbb, 0x10, 0x40, 0x4f, 0x2d, 0x1e, 0x08,
+        0x6b, 0xe4, 0x30, 0x21, 0x03, 0x59, 0xef, 0x50, 0x21, 0x96, 0x4f, 0xe2, 0x2d, 0x6f, 0x8e, 0x05,
+        0xb2, 0x46, 0x3c, 0x95, 0x40, 0xce, 0x96, 0x88, 0x3f, 0xe3, 0xb2, 0x78, 0x76, 0x0f, 0x04, 0x8f,
+        0x51, 0x89, 0xf2, 0xe6, 0xc4, 0x52, 0xae
+    ])
+};
+
+//Regtest doesn't retarget the difficulty, every block uses the maximum target, timestamp isn't checked
+// against the current time, as regtest nodes are commonly used with mocked time (setmocktime)
+#[cfg(feature = "bitcoin_regtest")]
+pub const PARAMS: ConsensusParams = ConsensusParams {
+    pow_limit: U256::from_be_bytes([
+        0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
+    ]),
+    pow_limit_nbits: 0x207fffff,
+    max_difficulty: MAX_DIFFICULTY,
+
+    diff_adjustment_interval: DIFF_ADJUSTMENT_INTERVAL,
+    target_timespan: TARGET_TIMESPAN,
+    no_retargeting: true,
+
+    //Regtest allows minimum difficulty blocks too, but as every block is mined
+    // with the minimum difficulty anyway, the rule has no effect
+    allow_min_difficulty_blocks: false,
+    min_difficulty_block_spacing: MIN_DIFFICULTY_BLOCK_SPACING,
+    enforce_bip94: false,
+
+    max_future_blocktime: None,
+
+    bip34_height: 1,
+
+    //0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206
+    reversed_genesis_block_hash: [
+        0x06, 0x22, 0x6e, 0x46, 0x11, 0x1a, 0x0b, 0x59, 0xca, 0xaf, 0x12, 0x60, 0x43, 0xeb, 0x5b, 0xbf,
+        0x28, 0xc3, 0x4f, 0x3a, 0x5e, 0x33, 0x2a, 0x1f, 0xc7, 0xb2, 0xb7, 0x3c, 0xf1, 0x88, 0x91, 0x0f
+    ],
+    signet_challenge: None
+};
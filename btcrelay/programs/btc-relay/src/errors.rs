@@ -31,5 +31,75 @@ pub enum RelayErrorCode {
     #[msg("Fork has invalid ID")]
     InvalidForkId,
     #[msg("Didn't pass enough remaining accounts!")]
-    InvalidRemainingAccounts
+    InvalidRemainingAccounts,
+    #[msg("Timestamp too low, timewarp protection.")]
+    ErrTimewarp,
+    #[msg("Instruction not supported on this bitcoin network")]
+    UnsupportedNetwork,
+    #[msg("Invalid coinbase transaction")]
+    InvalidCoinbase,
+    #[msg("Invalid signet block solution")]
+    InvalidSignetSolution,
+    #[msg("Block doesn't match the network's genesis block")]
+    InvalidGenesisBlock,
+    #[msg("Invalid MMR inclusion proof")]
+    InvalidMmrProof,
+    #[msg("Account cannot be written to")]
+    InvalidAccountWritability,
+    #[msg("Invalid data account")]
+    InvalidDataAccount,
+    #[msg("Main state has to be migrated with resize_main_state")]
+    MainStateNotMigrated,
+    #[msg("Ring buffer already has the required length")]
+    InvalidPruningFactor,
+    #[msg("Fork buffer is full")]
+    ForkBufferFull,
+    #[msg("Signer is not the fork's creator")]
+    InvalidForkCreator,
+    #[msg("Fork can still become the main chain")]
+    ForkNotStale,
+    #[msg("Signer is not the fee pool's authority")]
+    InvalidFeePoolAuthority,
+    #[msg("Submitter rewards PDA doesn't belong to the signer")]
+    InvalidSubmitterRewards,
+    #[msg("Reorg is too deep, has to be submitted through the fork PDA")]
+    DeepReorg,
+    #[msg("Deep reorg is pending")]
+    DeepReorgPending,
+    #[msg("Relay tip is too old")]
+    RelayStale,
+    #[msg("Invalid median time past")]
+    InvalidMedianTimePast,
+    #[msg("Invalid return data")]
+    InvalidReturnData,
+    #[msg("Invalid transaction")]
+    InvalidTransaction,
+    #[msg("Invalid transaction output index")]
+    InvalidVout,
+    #[msg("Transaction output script doesn't match")]
+    InvalidOutputScript,
+    #[msg("Transaction output value is too low")]
+    InsufficientOutputValue,
+    #[msg("Invalid partial merkle tree")]
+    InvalidPartialMerkleTree,
+    #[msg("Invalid witness commitment")]
+    InvalidWitnessCommitment,
+    #[msg("Coinbase doesn't commit to the block height")]
+    InvalidCoinbaseHeight,
+    #[msg("Merkle proof doesn't match the tree depth")]
+    InvalidMerkleProofDepth,
+    #[msg("Pruning factor out of the allowed range")]
+    PruningFactorOutOfRange,
+    #[msg("Fee pool balance too low")]
+    InsufficientFeePoolBalance,
+    #[msg("Fork is not the pending deep reorg fork")]
+    ForkNotPending,
+    #[msg("Fork is stale and can no longer become the main chain")]
+    ForkTooOld,
+    #[msg("Signer is not the program's upgrade authority")]
+    InvalidUpgradeAuthority,
+    #[msg("Shared fork already has the maximum number of contributors")]
+    ForkContributorsFull,
+    #[msg("Contribution to the shared fork is too low")]
+    ForkContributionTooLow
 }
@@ -23,3 +23,12 @@ pub struct ChainReorg {
     pub tip_block_hash: [u8; 32],
     pub tip_commit_hash: [u8; 32]
 }
+
+#[event]
+pub struct DeepReorgPending {
+    pub fork_id: u64,
+    pub start_height: u32,
+    pub tip_block_hash: [u8; 32],
+    pub tip_commit_hash: [u8; 32],
+    pub pending_since: u32
+}
@@ -1,11 +1,19 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{
+    prelude::*,
+    solana_program::bpf_loader_upgradeable
+};
 
 use crate::structs::*;
 use crate::state::*;
 
 #[derive(Accounts)]
 #[instruction(
-    data: BlockHeader
+    data: BlockHeader,
+    block_height: u32,
+    chain_work: [u8; 32],
+    last_diff_adjustment: u32,
+    prev_block_timestamps: [u32; 10],
+    pruning_factor: u32
 )]
 pub struct Initialize<'info> {
     #[account(mut)]
@@ -16,7 +24,7 @@ pub struct Initialize<'info> {
         seeds = [b"state".as_ref()],
         bump,
         payer = signer,
-        space = MainState::space()
+        space = MainState::space(pruning_factor)
     )]
     pub main_state: AccountLoader<'info, MainState>,
 
@@ -58,7 +66,7 @@ pub struct SubmitShortForkHeaders<'info> {
 
 #[derive(Accounts)]
 #[instruction(
-    headers: Vec<BlockHeader>,
+    data: HeadersData,
     commited_header: CommittedBlockHeader,
     fork_id: u64
 )]
@@ -78,13 +86,44 @@ pub struct SubmitForkHeaders<'info> {
         seeds = [b"fork".as_ref(), fork_id.to_le_bytes().as_ref(), signer.key.to_bytes().as_ref()],
         bump,
         payer = signer,
-        space = ForkState::space()
+        //Fork PDA is grown as the fork is extended, so an already existing PDA is expected to keep its size
+        space = ForkState::space(0).max(fork_state.data_len())
     )]
     pub fork_state: AccountLoader<'info, ForkState>,
 
     pub system_program: Program<'info, System>
 }
 
+#[derive(Accounts)]
+#[instruction(
+    data: HeadersData,
+    commited_header: CommittedBlockHeader,
+    fork_id: u64
+)]
+pub struct SubmitSharedForkHeaders<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>,
+
+    #[account(
+        init_if_needed,
+        seeds = [b"shared_fork".as_ref(), fork_id.to_le_bytes().as_ref()],
+        bump,
+        payer = signer,
+        //Fork PDA is grown as the fork is extended, so an already existing PDA is expected to keep its size
+        space = SharedForkState::space(0).max(fork_state.data_len())
+    )]
+    pub fork_state: AccountLoader<'info, SharedForkState>,
+
+    pub system_program: Program<'info, System>
+}
+
 #[derive(Accounts)]
 #[instruction(
     fork_id: u64
@@ -93,6 +132,13 @@ pub struct CloseForkAccount<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
 
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>,
+
     #[account(
         mut,
         seeds = [b"fork".as_ref(), fork_id.to_le_bytes().as_ref(), signer.key.to_bytes().as_ref()],
@@ -104,6 +150,209 @@ pub struct CloseForkAccount<'info> {
     pub system_program: Program<'info, System>
 }
 
+#[derive(Accounts)]
+#[instruction(
+    fork_id: u64
+)]
+pub struct CloseSharedForkAccount<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>,
+
+    #[account(
+        mut,
+        seeds = [b"shared_fork".as_ref(), fork_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub fork_state: AccountLoader<'info, SharedForkState>
+}
+
+#[derive(Accounts)]
+#[instruction(
+    fork_id: u64
+)]
+pub struct CloseStaleForkAccount<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>,
+
+    /// CHECK: This is only used as a rent refund destination, it is checked by the fork PDA's seeds
+    #[account(mut)]
+    pub creator: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"fork".as_ref(), fork_id.to_le_bytes().as_ref(), creator.key.to_bytes().as_ref()],
+        bump
+    )]
+    pub fork_state: AccountLoader<'info, ForkState>
+}
+
+#[derive(Accounts)]
+#[instruction(
+    fork_id: u64
+)]
+pub struct CloseStaleSharedForkAccount<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>,
+
+    #[account(
+        mut,
+        seeds = [b"shared_fork".as_ref(), fork_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub fork_state: AccountLoader<'info, SharedForkState>
+}
+
+#[derive(Accounts)]
+#[instruction(
+    fork_id: u64
+)]
+pub struct ApplyPendingFork<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>,
+
+    /// CHECK: This is only used as a rent refund destination, it is checked by the fork PDA's seeds
+    #[account(mut)]
+    pub creator: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"fork".as_ref(), fork_id.to_le_bytes().as_ref(), creator.key.to_bytes().as_ref()],
+        bump
+    )]
+    pub fork_state: AccountLoader<'info, ForkState>
+}
+
+#[derive(Accounts)]
+#[instruction(
+    fork_id: u64
+)]
+pub struct ApplyPendingSharedFork<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>,
+
+    #[account(
+        mut,
+        seeds = [b"shared_fork".as_ref(), fork_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub fork_state: AccountLoader<'info, SharedForkState>
+}
+
+#[derive(Accounts)]
+pub struct InitFeePool<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        init,
+        seeds = [b"fee_pool".as_ref()],
+        bump,
+        payer = signer,
+        space = 8+std::mem::size_of::<FeePool>()
+    )]
+    pub fee_pool: AccountLoader<'info, FeePool>,
+
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct SetHeaderReward<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_pool".as_ref()],
+        bump
+    )]
+    pub fee_pool: AccountLoader<'info, FeePool>
+}
+
+#[derive(Accounts)]
+pub struct FundFeePool<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_pool".as_ref()],
+        bump
+    )]
+    pub fee_pool: AccountLoader<'info, FeePool>,
+
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFeePool<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_pool".as_ref()],
+        bump
+    )]
+    pub fee_pool: AccountLoader<'info, FeePool>
+}
+
+#[derive(Accounts)]
+pub struct InitSubmitterRewards<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        init,
+        seeds = [b"rewards".as_ref(), signer.key.to_bytes().as_ref()],
+        bump,
+        payer = signer,
+        space = 8+std::mem::size_of::<SubmitterRewards>()
+    )]
+    pub submitter_rewards: AccountLoader<'info, SubmitterRewards>,
+
+    pub system_program: Program<'info, System>
+}
+
 #[derive(Accounts)]
 pub struct VerifyTransaction<'info> {
     #[account(mut)]
@@ -117,6 +366,118 @@ pub struct VerifyTransaction<'info> {
     pub main_state: AccountLoader<'info, MainState>
 }
 
+#[derive(Accounts)]
+pub struct VerifyTransactionLinked<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[cfg(not(feature = "mocked"))]
+    #[account(
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>,
+
+    //Data storage account
+    /// CHECK: This is checked in the instruction logic, the signer key has to be the first 32 bytes of the account data
+    pub data: UncheckedAccount<'info>
+}
+
+#[derive(Accounts)]
+pub struct VerifyRawTransactionData<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[cfg(not(feature = "mocked"))]
+    #[account(
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>,
+
+    //Data storage account
+    /// CHECK: This is checked in the instruction logic, the signer key has to be the first 32 bytes of the account data
+    pub data: UncheckedAccount<'info>
+}
+
+#[derive(Accounts)]
+pub struct InitData<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    //Data storage account
+    #[account(mut)]
+    pub data: Signer<'info>
+}
+
+#[derive(Accounts)]
+pub struct WriteData<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    //Data storage account
+    /// CHECK: This is checked in the instruction logic, the signer key has to be the first 32 bytes of the account data
+    #[account(mut)]
+    pub data: UncheckedAccount<'info>
+}
+
+#[derive(Accounts)]
+pub struct CloseData<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    //Data storage account
+    /// CHECK: This is checked in the instruction logic, the signer key has to be the first 32 bytes of the account data
+    #[account(mut)]
+    pub data: UncheckedAccount<'info>
+}
+
+#[derive(Accounts)]
+pub struct ResizeMainState<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>,
+
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct VerifyLiveness<'info> {
+    pub signer: Signer<'info>,
+
+    #[cfg(not(feature = "mocked"))]
+    #[account(
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>
+}
+
+#[derive(Accounts)]
+pub struct VerifyMedianTimePast<'info> {
+    pub signer: Signer<'info>,
+
+    #[cfg(not(feature = "mocked"))]
+    #[account(
+        seeds = [b"state".as_ref()],
+        bump
+    )]
+    pub main_state: AccountLoader<'info, MainState>
+}
+
 #[derive(Accounts)]
 pub struct BlockHeight<'info> {
     #[account(mut)]
@@ -0,0 +1,62 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::*;
+use crate::state::*;
+use crate::utils;
+use crate::ixs::shared_fork;
+
+//Share of the reclaimed rent of a stale fork PDA paid to the caller of the permissionless cleanup, in basis points
+pub const STALE_FORK_BOUNTY_BPS: u64 = 500;
+
+//Checks that the fork can no longer become the main chain, that is either the fork is stale (its start was already pruned
+// from the main chain, or it has no more chain work than the main chain and wasn't extended for STALE_FORK_TIMEOUT seconds),
+// or the fork's tip is already part of the main chain, stale forks can no longer be extended or applied
+fn check_fork_stale<T: ForkAccount>(main_state: &AccountLoader<MainState>, fork_state: &AccountLoader<T>) -> Result<()> {
+    let main_state = MainStateRef::load(main_state)?;
+    let fork_state = fork_state.load()?;
+    let fork = fork_state.fork();
+
+    let stale = fork.is_stale(&main_state, utils::now_ts()?);
+
+    //Fork's tip is guaranteed to be within the ring buffer here, if the fork is not stale
+    let tip_height = fork.start_height+fork.length;
+    let superseded = !stale && fork.length>0 && tip_height<=main_state.block_height &&
+        main_state.get_commitment(tip_height) == fork.tip_commit_hash;
+
+    require!(
+        stale || superseded,
+        RelayErrorCode::ForkNotStale
+    );
+
+    Ok(())
+}
+
+//Pays out STALE_FORK_BOUNTY_BPS share of the fork PDA's lamports to the signer
+fn pay_bounty(fork_account: &AccountInfo, signer: &Signer) -> Result<()> {
+    let bounty = fork_account.lamports()*STALE_FORK_BOUNTY_BPS/10000;
+    **fork_account.try_borrow_mut_lamports()? -= bounty;
+    **signer.to_account_info().try_borrow_mut_lamports()? += bounty;
+    Ok(())
+}
+
+//Processes permissionless closing of the stale fork PDA, the signer gets the bounty, rest of the rent is refunded to the creator
+pub fn process_close_stale_fork_account<'info>(main_state: &AccountLoader<'info, MainState>, fork_state: &AccountLoader<'info, ForkState>, signer: &Signer<'info>, creator: &AccountInfo<'info>, fork_id: u64) -> Result<()> {
+    check_fork_stale(main_state, fork_state)?;
+    MainStateRefMut::load_mut(main_state)?.clear_pending_fork(fork_id);
+
+    pay_bounty(&fork_state.to_account_info(), signer)?;
+    fork_state.close(creator.clone())
+}
+
+//Processes permissionless closing of the stale shared fork PDA, the signer gets the bounty, rest of the rent is refunded
+// to the fork's contributors proportionally to their contributions, remaining accounts are the accounts of the fork's contributors
+pub fn process_close_stale_shared_fork_account<'info>(main_state: &AccountLoader<'info, MainState>, fork_state: &AccountLoader<'info, SharedForkState>, signer: &Signer<'info>, remaining_accounts: &[AccountInfo<'info>], fork_id: u64) -> Result<()> {
+    check_fork_stale(main_state, fork_state)?;
+    MainStateRefMut::load_mut(main_state)?.clear_pending_fork(fork_id);
+
+    let contributors = fork_state.load()?.get_contributors().to_vec();
+
+    let fork_account_info = fork_state.to_account_info();
+    pay_bounty(&fork_account_info, signer)?;
+    shared_fork::close_shared_fork(&fork_account_info, &contributors, remaining_accounts, &signer.to_account_info())
+}
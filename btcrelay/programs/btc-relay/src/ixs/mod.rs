@@ -0,0 +1,6 @@
+pub mod submit;
+pub mod resize;
+pub mod shared_fork;
+pub mod cleanup;
+pub mod rewards;
+pub mod verify;
@@ -0,0 +1,84 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE
+};
+
+use crate::errors::*;
+use crate::state::*;
+use crate::utils;
+use crate::u256::U256;
+
+//Processes resizing of the main state PDA's ring buffer to the requested pruning factor, migrating the legacy main
+// state PDA to the current layout first, current window of block commitments is preserved, ring buffer can only grow
+//Account data can only grow by MAX_PERMITTED_DATA_INCREASE bytes per instruction, so this might need
+// to be called multiple times (with the same pruning factor) to reach the requested pruning factor
+//Only the program's upgrade authority can change the pruning factor, as it affects all the relay's consumers,
+// migrating the legacy main state PDA while keeping its pruning factor is permissionless
+pub fn process_resize_main_state<'info>(main_state: &AccountLoader<'info, MainState>, signer: &Signer<'info>, program_data: &Account<'info, ProgramData>, system_program: &Program<'info, System>, pruning_factor: u32) -> Result<()> {
+    require!(
+        (MIN_PRUNING_FACTOR..=MAX_PRUNING_FACTOR).contains(&pruning_factor),
+        RelayErrorCode::PruningFactorOutOfRange
+    );
+
+    let account_info = main_state.to_account_info();
+    let old_len = account_info.data_len();
+    let legacy = old_len == LEGACY_SPACE;
+
+    let old_pruning_factor = if legacy {
+        LEGACY_PRUNING_FACTOR
+    } else {
+        MainStateRef::load(main_state)?.pruning_factor
+    };
+
+    require!(
+        legacy || old_pruning_factor < pruning_factor,
+        RelayErrorCode::InvalidPruningFactor
+    );
+
+    require!(
+        (legacy && pruning_factor == LEGACY_PRUNING_FACTOR) || program_data.upgrade_authority_address == Some(signer.key()),
+        RelayErrorCode::InvalidUpgradeAuthority
+    );
+
+    let max_pruning_factor = ((old_len+MAX_PERMITTED_DATA_INCREASE-MainState::space(0))/32) as u32;
+    let new_pruning_factor = pruning_factor.min(max_pruning_factor);
+    let new_len = MainState::space(new_pruning_factor);
+
+    utils::realloc_account(&account_info, signer, system_program, new_len)?;
+
+    let mut data = account_info.try_borrow_mut_data()?;
+
+    if legacy {
+        //Move the ring buffer right after the current MainState struct & zero out the new fields
+        let legacy_ring_buffer_start = 8+LEGACY_HEADER_SIZE;
+        let legacy_ring_buffer_end = legacy_ring_buffer_start+(LEGACY_PRUNING_FACTOR as usize*32);
+        data.copy_within(legacy_ring_buffer_start..legacy_ring_buffer_end, MainState::space(0));
+        data[legacy_ring_buffer_start..MainState::space(0)].fill(0);
+    }
+
+    let (state, block_commitments) = data[8..new_len].split_at_mut(MainState::space(0)-8);
+    let state: &mut MainState = bytemuck::from_bytes_mut(state);
+    let block_commitments: &mut [[u8; 32]] = bytemuck::cast_slice_mut(block_commitments);
+
+    //Re-lay out the current window of the ring buffer linearly, with the oldest block at position 0,
+    // the newly added positions are then filled up by the next blocks
+    state.pruning_factor = old_pruning_factor;
+    let oldest_height = (state.block_height+1).saturating_sub(old_pruning_factor);
+    let oldest_position = state.get_position(oldest_height);
+    block_commitments[..old_pruning_factor as usize].rotate_left(oldest_position);
+    state.start_height = oldest_height;
+    state.pruning_factor = new_pruning_factor;
+
+    if legacy {
+        //Block commitments already pruned from the legacy ring buffer are lost, so the MMR accumulator
+        // starts at the oldest block commitment still stored in the ring buffer
+        let empty_positions = block_commitments.iter().take_while(|commitment| **commitment==[0; 32]).count() as u32;
+        state.mmr_start_height = oldest_height+empty_positions;
+        state.mmr_leaf_count = 0;
+
+        //Legacy main state PDA stored the chain work as 32-byte big endian array at the same offset
+        state.chain_work = U256::from_be_bytes(bytemuck::cast(state.chain_work));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use anchor_lang::{
+    prelude::*,
+    ZeroCopy
+};
+use std::cell::RefMut;
+
+use crate::errors::*;
+use crate::state::*;
+
+//Loads zero copy account of the program passed in the remaining accounts
+fn load_account_mut<'a, T: ZeroCopy + Owner>(account: &'a AccountInfo) -> Result<RefMut<'a, T>> {
+    require!(
+        account.owner == &T::owner(),
+        ErrorCode::AccountOwnedByWrongProgram
+    );
+    require!(
+        account.is_writable,
+        RelayErrorCode::InvalidAccountWritability
+    );
+    let data = account.try_borrow_mut_data()?;
+    let size = 8+std::mem::size_of::<T>();
+    require!(
+        data.len() >= size && data[0..8] == T::discriminator(),
+        ErrorCode::AccountDiscriminatorMismatch
+    );
+    Ok(RefMut::map(data, |data| bytemuck::from_bytes_mut(&mut data[8..size])))
+}
+
+//Splits the accounts following the header topics in the remaining accounts into the optional fee pool & submitter rewards
+// PDAs, which have to be passed first, and the rest of the accounts, fee pool PDA is recognized by its address
+pub fn split_reward_accounts<'a, 'info>(accounts: &'a [AccountInfo<'info>], program_id: &Pubkey) -> (&'a [AccountInfo<'info>], &'a [AccountInfo<'info>]) {
+    if accounts.len() >= 2 && *accounts[0].key == Pubkey::find_program_address(&[b"fee_pool".as_ref()], program_id).0 {
+        accounts.split_at(2)
+    } else {
+        accounts.split_at(0)
+    }
+}
+
+//Pays out the header reward for every accepted block header from the fee pool to the signer, limited by the fee pool's
+// balance above its rent exempt minimum, reward_accounts are either empty (no reward is requested),
+// or the fee pool & signer's submitter rewards PDAs
+pub fn pay_header_rewards(reward_accounts: &[AccountInfo], signer: &Signer, header_count: u32) -> Result<()> {
+    if reward_accounts.is_empty() {
+        return Ok(());
+    }
+
+    require!(
+        reward_accounts.len() == 2,
+        RelayErrorCode::InvalidRemainingAccounts
+    );
+
+    let fee_pool_info = &reward_accounts[0];
+    let mut fee_pool = load_account_mut::<FeePool>(fee_pool_info)?;
+    let mut submitter_rewards = load_account_mut::<SubmitterRewards>(&reward_accounts[1])?;
+
+    require!(
+        submitter_rewards.submitter == signer.key(),
+        RelayErrorCode::InvalidSubmitterRewards
+    );
+
+    let available_lamports = fee_pool_info.lamports().saturating_sub(Rent::get()?.minimum_balance(fee_pool_info.data_len()));
+    let reward = fee_pool.header_reward.saturating_mul(header_count as u64).min(available_lamports);
+
+    **fee_pool_info.try_borrow_mut_lamports()? -= reward;
+    **signer.to_account_info().try_borrow_mut_lamports()? += reward;
+
+    fee_pool.total_headers += header_count as u64;
+    fee_pool.total_rewards += reward;
+    submitter_rewards.total_headers += header_count as u64;
+    submitter_rewards.total_rewards += reward;
+
+    Ok(())
+}
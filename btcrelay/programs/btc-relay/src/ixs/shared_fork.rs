@@ -0,0 +1,123 @@
+use anchor_lang::{
+    prelude::*,
+    system_program
+};
+
+use crate::errors::*;
+use crate::state::*;
+use crate::structs::*;
+use crate::utils;
+use crate::ixs::{submit, rewards};
+
+//Processes submission of fork blockheaders to the shared fork PDA, same as process_submit_fork_headers, but any submitter
+// can append to the fork, lamports paid for the PDA's rent are tracked per contributor
+//Remaining accounts are the header topics (unless skipped with header_topics false), optionally followed by the fee pool & submitter rewards PDAs, followed by the accounts
+// of the fork's contributors (in the order of SharedForkState.contributors), contributor accounts are only required when the fork
+// overtakes the main chain, in which case the PDA is closed and its rent refunded to the contributors proportionally
+// to their contributions, only the blockheaders submitted in the winning transaction, which extend the main chain beyond
+// its previous tip are then rewarded from the fee pool
+#[allow(clippy::too_many_arguments)]
+pub fn process_submit_shared_fork_headers<'info>(main_state: &AccountLoader<'info, MainState>, fork_state: &AccountLoader<'info, SharedForkState>, signer: &Signer<'info>, system_program: &Program<'info, System>, remaining_accounts: &[AccountInfo<'info>], program_id: &Pubkey, data: &HeadersData, commited_header: CommittedBlockHeader, fork_id: u64, init: bool, header_topics: bool) -> Result<()> {
+    let prev_header = HashedBlockHeader::new(commited_header.header)?;
+    let headers = utils::decode_headers(data, &prev_header)?;
+
+    let (header_topics, remaining_accounts) = submit::split_header_topics(remaining_accounts, headers.len(), header_topics)?;
+    let (reward_accounts, contributor_accounts) = rewards::split_reward_accounts(remaining_accounts, program_id);
+
+    let fork_account_info = fork_state.to_account_info();
+
+    //Rent for the PDA creation was already paid by the signer when initializing
+    let starting_lamports = if init {
+        0
+    } else {
+        fork_account_info.lamports()
+    };
+
+    let opt_new_headers = submit::submit_fork_headers(main_state, fork_state, signer, system_program, header_topics, program_id, &headers, commited_header, prev_header.block_hash, fork_id, init)?;
+
+    let contributed_lamports = fork_account_info.lamports()-starting_lamports;
+
+    let contributors = {
+        let fork_state = &mut ForkStateRefMut::load(fork_state, init)?;
+        if opt_new_headers.is_none() {
+            fork_state.account_mut().add_contribution(signer.key, contributed_lamports)?;
+        }
+        fork_state.account().get_contributors().to_vec()
+    };
+
+    if let Some(new_headers) = opt_new_headers {
+        //Lamports paid in this instruction are returned straight to the signer
+        **fork_account_info.try_borrow_mut_lamports()? -= contributed_lamports;
+        **signer.to_account_info().try_borrow_mut_lamports()? += contributed_lamports;
+
+        close_shared_fork(&fork_account_info, &contributors, contributor_accounts, &signer.to_account_info())?;
+        msg!("Account closed");
+
+        rewards::pay_header_rewards(reward_accounts, signer, new_headers.min(headers.len() as u32))?;
+    }
+
+    Ok(())
+}
+
+//Processes permissionless applying of the pending deep reorg shared fork after its challenge delay, the PDA is then closed
+// and its rent refunded to the contributors, remaining accounts are the accounts of the fork's contributors
+pub fn process_apply_pending_shared_fork<'info>(main_state: &AccountLoader<'info, MainState>, fork_state: &AccountLoader<'info, SharedForkState>, signer: &Signer<'info>, remaining_accounts: &[AccountInfo<'info>], fork_id: u64, tip_header: CommittedBlockHeader) -> Result<()> {
+    submit::apply_pending_fork(main_state, fork_state, fork_id, tip_header)?;
+
+    let contributors = fork_state.load()?.get_contributors().to_vec();
+    close_shared_fork(&fork_state.to_account_info(), &contributors, remaining_accounts, &signer.to_account_info())?;
+    msg!("Account closed");
+
+    Ok(())
+}
+
+//Processes closing of the shared fork PDA by its creator, abandoning the fork, rent is refunded to the contributors
+// proportionally to their contributions, remaining accounts are the accounts of the fork's contributors
+pub fn process_close_shared_fork_account<'info>(main_state: &AccountLoader<'info, MainState>, fork_state: &AccountLoader<'info, SharedForkState>, signer: &Signer<'info>, remaining_accounts: &[AccountInfo<'info>], fork_id: u64) -> Result<()> {
+    let contributors = fork_state.load()?.get_contributors().to_vec();
+
+    require!(
+        contributors.first().map(|creator| creator.submitter) == Some(signer.key()),
+        RelayErrorCode::InvalidForkCreator
+    );
+
+    MainStateRefMut::load_mut(main_state)?.clear_pending_fork(fork_id);
+
+    close_shared_fork(&fork_state.to_account_info(), &contributors, remaining_accounts, &signer.to_account_info())
+}
+
+//Closes the shared fork PDA, splitting its lamports between the contributors proportionally to their contributions,
+// contributor_accounts need to be passed in the same order as contributors, rounding remainder goes to the remainder_destination
+pub fn close_shared_fork<'info>(fork_account: &AccountInfo<'info>, contributors: &[ForkContributor], contributor_accounts: &[AccountInfo<'info>], remainder_destination: &AccountInfo<'info>) -> Result<()> {
+    require!(
+        contributors.len() == contributor_accounts.len(),
+        RelayErrorCode::InvalidRemainingAccounts
+    );
+
+    let total_lamports = fork_account.lamports();
+    let total_contributed: u128 = contributors.iter().map(|contributor| contributor.lamports as u128).sum();
+
+    let mut remaining_lamports = total_lamports;
+    for (contributor, account) in contributors.iter().zip(contributor_accounts.iter()) {
+        require!(
+            account.key() == contributor.submitter,
+            RelayErrorCode::InvalidRemainingAccounts
+        );
+        require!(
+            account.is_writable,
+            RelayErrorCode::InvalidAccountWritability
+        );
+
+        let refund = (total_lamports as u128 * contributor.lamports as u128 / total_contributed) as u64;
+        remaining_lamports -= refund;
+        **account.try_borrow_mut_lamports()? += refund;
+    }
+
+    **remainder_destination.try_borrow_mut_lamports()? += remaining_lamports;
+    **fork_account.try_borrow_mut_lamports()? = 0;
+
+    fork_account.assign(&system_program::ID);
+    fork_account.realloc(0, false)?;
+
+    Ok(())
+}
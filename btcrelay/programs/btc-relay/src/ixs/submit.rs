@@ -0,0 +1,449 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::*;
+use crate::state::*;
+use crate::events::*;
+use crate::structs::*;
+use crate::utils;
+use crate::u256::U256;
+use crate::ixs::rewards;
+
+//Reorgs replacing more than DEEP_REORG_THRESHOLD main chain blocks only become pending, and are applied after the fork
+// extends its lead over the main chain to at least DEEP_REORG_LEAD blocks, or after the fork with more chain work than
+// the main chain was pending for DEEP_REORG_CHALLENGE_DELAY seconds, only the pending fork with the most chain work is
+// tracked (along with the timestamp since when that fork is pending), it stops being pending when the main chain's work
+// catches up with it, or when the fork is applied or closed
+pub const DEEP_REORG_THRESHOLD: u32 = 6;
+pub const DEEP_REORG_LEAD: u32 = 6;
+pub const DEEP_REORG_CHALLENGE_DELAY: u32 = 2*60*60;
+
+//Splits the remaining accounts into the header topics (one for every blockheader) and the accounts following them,
+// when header_topics is false no header topics are expected in the remaining accounts (and none are checked)
+pub fn split_header_topics<'a, 'info>(remaining_accounts: &'a [AccountInfo<'info>], header_count: usize, header_topics: bool) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    let topics_len = if header_topics { header_count } else { 0 };
+    require!(
+        topics_len <= remaining_accounts.len(),
+        RelayErrorCode::InvalidRemainingAccounts
+    );
+
+    Ok(remaining_accounts.split_at(topics_len))
+}
+
+//Processes submission of new main chain blockheaders, verifies & stores the blockheaders, emits StoreHeader events
+pub fn process_submit_block_headers(main_state: &AccountLoader<MainState>, signer: &Signer, remaining_accounts: &[AccountInfo], program_id: &Pubkey, data: &HeadersData, commited_header: CommittedBlockHeader, header_topics: bool) -> Result<()> {
+    let prev_header = HashedBlockHeader::new(commited_header.header)?;
+    let headers = utils::decode_headers(data, &prev_header)?;
+
+    //Header topics are optionally followed by the fee pool & submitter rewards PDAs
+    let (remaining_accounts, reward_accounts) = split_header_topics(remaining_accounts, headers.len(), header_topics)?;
+
+    //Verify commited header was indeed committed
+    let commit_hash = commited_header.get_commit_hash()?;
+    let main_state = &mut MainStateRefMut::load_mut(main_state)?;
+    let main_state_tip = main_state.get_commitment(main_state.block_height);
+    require!(
+        commit_hash == main_state_tip,
+        RelayErrorCode::PrevBlockCommitment
+    );
+
+    let mut last_commited_header = commited_header;
+    let mut last_block_hash: [u8; 32] = prev_header.block_hash;
+    let mut block_height = main_state.block_height;
+    let mut block_commit_hash: [u8; 32] = [0; 32];
+
+    for (block_cnt, header) in headers.iter().enumerate() {
+        //Prev block hash matches
+        require!(
+            last_block_hash == header.header.reversed_prev_blockhash,
+            RelayErrorCode::PrevBlock
+        );
+
+        block_height+=1;
+
+        //Header topics slice is empty when the header topics are skipped
+        utils::verify_header(
+            header,
+            &mut last_commited_header,
+            remaining_accounts.get(block_cnt),
+            signer,
+            program_id
+        )?;
+        last_block_hash = header.block_hash;
+        
+        //Compute commit hash
+        block_commit_hash = last_commited_header.get_commit_hash()?;
+
+        //Store and emit
+        main_state.store_block_commitment(block_height, block_commit_hash);
+        emit!(StoreHeader {
+            block_hash: last_block_hash,
+            commit_hash: block_commit_hash,
+            header: last_commited_header
+        });
+    }
+
+    //Update globals
+    main_state.last_diff_adjustment = last_commited_header.last_diff_adjustment;
+    main_state.block_height = block_height;
+    main_state.chain_work = U256::from_be_bytes(last_commited_header.chain_work);
+    main_state.tip_commit_hash = block_commit_hash;
+    main_state.tip_block_hash = last_block_hash;
+    main_state.update_pending_fork();
+
+    rewards::pay_header_rewards(reward_accounts, signer, headers.len() as u32)?;
+
+    Ok(())
+}
+
+//Processes submission of short fork blockheaders, verifies & stores the blockheaders, emits StoreFork events,
+// fails if the fork's chain work doesn't exceed main chain's work
+pub fn process_submit_short_fork_headers(main_state: &AccountLoader<MainState>, signer: &Signer, remaining_accounts: &[AccountInfo], program_id: &Pubkey, data: &HeadersData, commited_header: CommittedBlockHeader, header_topics: bool) -> Result<()> {
+    let prev_header = HashedBlockHeader::new(commited_header.header)?;
+    let headers = utils::decode_headers(data, &prev_header)?;
+
+    //Header topics are optionally followed by the fee pool & submitter rewards PDAs
+    let (remaining_accounts, reward_accounts) = split_header_topics(remaining_accounts, headers.len(), header_topics)?;
+
+    //Verify commited header was indeed committed
+    let commit_hash = commited_header.get_commit_hash()?;
+
+    let main_state = &mut MainStateRefMut::load_mut(main_state)?;
+    let prev_block_height = main_state.block_height;
+
+    require!(
+        commit_hash == main_state.get_commitment(commited_header.blockheight),
+        RelayErrorCode::PrevBlockCommitment
+    );
+
+    //Deep reorgs have to go through the fork PDA, where they can be made pending
+    require!(
+        main_state.block_height.saturating_sub(commited_header.blockheight) <= DEEP_REORG_THRESHOLD,
+        RelayErrorCode::DeepReorg
+    );
+
+    let fork_id = main_state.fork_counter;
+    let mut last_commited_header = commited_header;
+    let mut last_block_hash: [u8; 32] = prev_header.block_hash;
+    let mut block_height = commited_header.blockheight;
+
+    let mut block_commit_hash: [u8; 32] = [0; 32];
+
+    for (block_cnt, header) in headers.iter().enumerate() {
+        //Prev block hash matches
+        require!(
+            last_block_hash == header.header.reversed_prev_blockhash,
+            RelayErrorCode::PrevBlock
+        );
+
+        block_height+=1;
+
+        utils::verify_header(
+            header,
+            &mut last_commited_header,
+            remaining_accounts.get(block_cnt),
+            signer,
+            program_id
+        )?;
+        last_block_hash = header.block_hash;
+        
+        //Compute commit hash
+        block_commit_hash = last_commited_header.get_commit_hash()?;
+
+        //Store and emit
+        main_state.store_block_commitment(block_height, block_commit_hash);
+        emit!(StoreFork {
+            fork_id,
+            block_hash: last_block_hash,
+            commit_hash: block_commit_hash,
+            header: last_commited_header
+        });
+    }
+
+    //Verify if fork chain's work exceeded main chain's work
+    require!(
+        U256::from_be_bytes(last_commited_header.chain_work) > main_state.chain_work,
+        RelayErrorCode::ForkTooShort
+    );
+
+    //Update globals
+    main_state.last_diff_adjustment = last_commited_header.last_diff_adjustment;
+    main_state.block_height = block_height;
+    main_state.chain_work = U256::from_be_bytes(last_commited_header.chain_work);
+    main_state.tip_commit_hash = block_commit_hash;
+    main_state.tip_block_hash = last_block_hash;
+    main_state.fork_counter = fork_id+1;
+    main_state.update_pending_fork();
+
+    //Only blockheaders extending the main chain beyond its previous tip are rewarded
+    rewards::pay_header_rewards(reward_accounts, signer, block_height.saturating_sub(prev_block_height))?;
+
+    Ok(())
+}
+
+//Processes submission of fork blockheaders to the fork PDA, verifies & stores the blockheaders, emits StoreFork events,
+// as soon as the fork's chain work exceeds main chain's work, the main chain is overwritten and fork PDA closed,
+// the fork's blockheaders extending the main chain beyond its previous tip are then rewarded from the fee pool
+#[allow(clippy::too_many_arguments)]
+pub fn process_submit_fork_headers<'info>(main_state: &AccountLoader<'info, MainState>, fork_state: &AccountLoader<'info, ForkState>, signer: &Signer<'info>, system_program: &Program<'info, System>, remaining_accounts: &[AccountInfo], program_id: &Pubkey, data: &HeadersData, commited_header: CommittedBlockHeader, fork_id: u64, init: bool, header_topics: bool) -> Result<()> {
+    let prev_header = HashedBlockHeader::new(commited_header.header)?;
+    let headers = utils::decode_headers(data, &prev_header)?;
+
+    //Header topics are optionally followed by the fee pool & submitter rewards PDAs
+    let (header_topics, reward_accounts) = split_header_topics(remaining_accounts, headers.len(), header_topics)?;
+
+    let opt_new_headers = submit_fork_headers(main_state, fork_state, signer, system_program, header_topics, program_id, &headers, commited_header, prev_header.block_hash, fork_id, init)?;
+
+    if let Some(new_headers) = opt_new_headers {
+        //Only blockheaders submitted in this instruction can be rewarded
+        rewards::pay_header_rewards(reward_accounts, signer, new_headers.min(headers.len() as u32))?;
+
+        fork_state.close(signer.to_account_info())?;
+        msg!("Account closed");
+    }
+
+    Ok(())
+}
+
+//Verifies & stores the fork blockheaders in the fork PDA of any type, growing the PDA as needed, emits StoreFork events,
+// as soon as the fork's chain work exceeds main chain's work, the main chain is overwritten, returns the number of blocks
+// the main chain got extended by beyond its previous tip when the fork was applied (fork PDA should then be closed), None otherwise
+//Header topics are either empty (skipped), or contain the topic of every blockheader
+#[allow(clippy::too_many_arguments)]
+pub fn submit_fork_headers<'info, T: ForkAccount>(main_state: &AccountLoader<'info, MainState>, fork_state: &AccountLoader<'info, T>, signer: &Signer<'info>, system_program: &Program<'info, System>, header_topics: &[AccountInfo], program_id: &Pubkey, data: &[HashedBlockHeader], commited_header: CommittedBlockHeader, commited_block_hash: [u8; 32], fork_id: u64, init: bool) -> Result<Option<u32>> {
+    require!(
+        !data.is_empty(),
+        RelayErrorCode::NoHeaders
+    );
+    
+    require!(
+        header_topics.is_empty() || data.len() == header_topics.len(),
+        RelayErrorCode::InvalidRemainingAccounts
+    );
+
+    let mut new_headers = None;
+
+    //Grow the fork PDA to fit the new block commitments
+    let fork_length = if init {
+        0
+    } else {
+        fork_state.load()?.fork().length
+    };
+    let required_len = T::space(fork_length+data.len() as u32);
+    let fork_account_info = fork_state.to_account_info();
+    if fork_account_info.data_len() < required_len {
+        utils::realloc_account(&fork_account_info, signer, system_program, required_len)?;
+    }
+
+    {
+
+        let fork_state = &mut ForkStateRefMut::load(fork_state, init)?;
+
+        //Only yet uninitialized PDA can be initialized
+        require!(
+            init == (fork_state.initialized==0),
+            RelayErrorCode::ErrInit
+        );
+
+        let main_state = &mut MainStateRefMut::load_mut(main_state)?;
+
+        let commit_hash = commited_header.get_commit_hash()?;
+
+        let mut block_height = commited_header.blockheight;
+
+        let timestamp = Clock::get()?.unix_timestamp as u32;
+
+        if fork_state.initialized==0 {
+            //Has to use new fork_id from the fork_counter
+            require!(
+                main_state.fork_counter == fork_id,
+                RelayErrorCode::InvalidForkId
+            );
+
+            main_state.fork_counter = fork_id+1;
+
+            //Verify commited header was indeed committed,
+            // the latest common ancestor block, right before the fork occurred
+            require!(
+                commit_hash == main_state.get_commitment(commited_header.blockheight),
+                RelayErrorCode::PrevBlockCommitment
+            );
+
+            fork_state.initialized = 1;
+            fork_state.start_height = block_height;
+            fork_state.tip_chain_work = U256::from_be_bytes(commited_header.chain_work);
+            fork_state.last_submission = timestamp;
+        } else {
+            //Verify commited header was indeed committed in the fork state
+            require!(
+                commit_hash == fork_state.tip_commit_hash,
+                RelayErrorCode::PrevBlockCommitment
+            );
+        }
+
+        //Stale fork can be closed by anyone, so it can't be extended anymore, checked on every extension,
+        // as the main chain might've advanced past the fork's start since the fork was created
+        require!(
+            !fork_state.is_stale(main_state, timestamp),
+            RelayErrorCode::ForkTooOld
+        );
+
+        let mut last_commited_header = commited_header;
+        let mut last_block_hash: [u8; 32] = commited_block_hash;
+
+        let mut block_commit_hash: [u8; 32] = [0; 32];
+
+        for (block_cnt, header) in data.iter().enumerate() {
+            //Prev block hash matches
+            require!(
+                last_block_hash == header.header.reversed_prev_blockhash,
+                RelayErrorCode::PrevBlock
+            );
+
+            block_height+=1;
+
+            utils::verify_header(header, &mut last_commited_header, header_topics.get(block_cnt), signer, program_id)?;
+            last_block_hash = header.block_hash;
+            
+            //Compute commit hash
+            block_commit_hash = last_commited_header.get_commit_hash()?;
+
+            //Store and emit
+            fork_state.store_block_commitment(block_commit_hash)?;
+            emit!(StoreFork {
+                fork_id,
+                block_hash: last_block_hash,
+                commit_hash: block_commit_hash,
+                header: last_commited_header
+            });
+        }
+
+        let fork_chain_work = U256::from_be_bytes(last_commited_header.chain_work);
+        let mut apply = fork_chain_work > main_state.chain_work;
+
+        let reorg_depth = main_state.block_height.saturating_sub(fork_state.start_height);
+        if apply && reorg_depth > DEEP_REORG_THRESHOLD && block_height < main_state.block_height + DEEP_REORG_LEAD {
+            //Deep reorg without enough lead, can only be applied after the fork was pending for the challenge delay,
+            // fork with more chain work replaces the currently pending fork, starting its own challenge delay
+            if !main_state.is_deep_reorg_pending() ||
+                (main_state.pending_fork_id != fork_id && fork_chain_work > main_state.pending_fork_chain_work) {
+                main_state.pending_fork_id = fork_id;
+                main_state.pending_fork_since = timestamp;
+            }
+
+            if main_state.pending_fork_id == fork_id {
+                main_state.pending_fork_chain_work = fork_chain_work;
+                if timestamp < main_state.pending_fork_since + DEEP_REORG_CHALLENGE_DELAY {
+                    apply = false;
+
+                    emit!(DeepReorgPending {
+                        fork_id,
+                        start_height: fork_state.start_height,
+                        tip_block_hash: last_block_hash,
+                        tip_commit_hash: block_commit_hash,
+                        pending_since: main_state.pending_fork_since
+                    });
+                }
+            } else {
+                //Another fork with more chain work is already pending
+                apply = false;
+            }
+        }
+
+        if apply {
+            new_headers = Some(apply_fork(main_state, fork_state, fork_id, &last_commited_header, last_block_hash, block_commit_hash)?);
+        } else {
+            //Fork still needs to be appended
+            fork_state.tip_block_hash = last_block_hash;
+            fork_state.tip_commit_hash = block_commit_hash;
+            fork_state.tip_chain_work = fork_chain_work;
+            fork_state.last_submission = timestamp;
+        }
+    }
+
+    Ok(new_headers)
+}
+
+//Overwrites the main chain with the fork (which has more chain work than the main chain), tip_header is the fork's tip,
+// returns the number of blocks the main chain got extended by beyond its previous tip
+fn apply_fork<T: ForkAccount>(main_state: &mut MainStateRefMut, fork_state: &ForkStateRefMut<T>, fork_id: u64, tip_header: &CommittedBlockHeader, tip_block_hash: [u8; 32], tip_commit_hash: [u8; 32]) -> Result<u32> {
+    msg!("Successful fork...");
+
+    //Overwrite block commitments in main chain
+    let start_height = fork_state.start_height;
+    main_state.apply_fork(start_height, fork_state.get_block_commitments())?;
+
+    msg!("Commitments stored...");
+
+    let block_height = start_height+fork_state.length;
+    let new_headers = block_height.saturating_sub(main_state.block_height);
+
+    //Update main state with fork's state
+    main_state.last_diff_adjustment = tip_header.last_diff_adjustment;
+    main_state.block_height = block_height;
+    main_state.chain_work = U256::from_be_bytes(tip_header.chain_work);
+    main_state.tip_commit_hash = tip_commit_hash;
+    main_state.tip_block_hash = tip_block_hash;
+    main_state.clear_pending_fork(fork_id);
+    main_state.update_pending_fork();
+
+    msg!("Main state updated");
+
+    emit!(ChainReorg {
+        fork_id,
+        start_height,
+        tip_block_hash,
+        tip_commit_hash
+    });
+
+    Ok(new_headers)
+}
+
+//Applies the pending deep reorg fork of any type, once its challenge delay has elapsed, tip_header is the fork's tip,
+// returns the number of blocks the main chain got extended by beyond its previous tip, fork PDA should then be closed
+pub fn apply_pending_fork<'info, T: ForkAccount>(main_state: &AccountLoader<'info, MainState>, fork_state: &AccountLoader<'info, T>, fork_id: u64, tip_header: CommittedBlockHeader) -> Result<u32> {
+    let fork_state = &ForkStateRefMut::load(fork_state, false)?;
+    let main_state = &mut MainStateRefMut::load_mut(main_state)?;
+
+    let tip_commit_hash = tip_header.get_commit_hash()?;
+    require!(
+        fork_state.length > 0 && tip_commit_hash == fork_state.tip_commit_hash,
+        RelayErrorCode::PrevBlockCommitment
+    );
+
+    require!(
+        main_state.is_deep_reorg_pending() && main_state.pending_fork_id == fork_id,
+        RelayErrorCode::ForkNotPending
+    );
+
+    let timestamp = Clock::get()?.unix_timestamp as u32;
+    require!(
+        timestamp >= main_state.pending_fork_since + DEEP_REORG_CHALLENGE_DELAY,
+        RelayErrorCode::DeepReorgPending
+    );
+
+    //Main chain might've advanced past the fork's start during the challenge delay
+    require!(
+        !fork_state.is_stale(main_state, timestamp),
+        RelayErrorCode::ForkTooOld
+    );
+
+    //Pending fork always has more chain work than the main chain
+    require!(
+        U256::from_be_bytes(tip_header.chain_work) > main_state.chain_work,
+        RelayErrorCode::ForkTooShort
+    );
+
+    let tip_block_hash = fork_state.tip_block_hash;
+    apply_fork(main_state, fork_state, fork_id, &tip_header, tip_block_hash, tip_commit_hash)
+}
+
+//Processes permissionless applying of the pending deep reorg fork after its challenge delay, the fork PDA is then
+// closed and its rent refunded to the creator, no headers are rewarded from the fee pool
+pub fn process_apply_pending_fork<'info>(main_state: &AccountLoader<'info, MainState>, fork_state: &AccountLoader<'info, ForkState>, creator: &AccountInfo<'info>, fork_id: u64, tip_header: CommittedBlockHeader) -> Result<()> {
+    apply_pending_fork(main_state, fork_state, fork_id, tip_header)?;
+
+    fork_state.close(creator.clone())?;
+    msg!("Account closed");
+
+    Ok(())
+}
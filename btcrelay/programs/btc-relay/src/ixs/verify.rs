@@ -0,0 +1,278 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::hash
+};
+
+use crate::errors::*;
+use crate::state::*;
+use crate::structs::*;
+use crate::btctx;
+use crate::consensus;
+use crate::merkleblock;
+use crate::utils;
+
+//Verifies that the commited header is stored in the main chain's ring buffer with certain amount of confirmations,
+// returns its current number of confirmations
+fn verify_commited_header(main_state: &AccountLoader<MainState>, confirmations: u32, commited_header: &CommittedBlockHeader) -> Result<u32> {
+    let block_height = commited_header.blockheight;
+
+    let main_state = MainStateRef::load(main_state)?;
+
+    //Main chain might get replaced by the pending deep reorg fork
+    require!(
+        !main_state.is_deep_reorg_pending(),
+        RelayErrorCode::DeepReorgPending
+    );
+
+    require!(
+        block_height <= main_state.block_height,
+        RelayErrorCode::BlockConfirmations
+    );
+
+    let current_confirmations = main_state.block_height - block_height + 1;
+    require!(
+        current_confirmations >= confirmations,
+        RelayErrorCode::BlockConfirmations
+    );
+
+    let commit_hash = commited_header.get_commit_hash()?;
+    require!(
+        commit_hash == main_state.get_commitment(block_height),
+        RelayErrorCode::PrevBlockCommitment
+    );
+
+    Ok(current_confirmations)
+}
+
+//Processes verification of the transaction block inclusion proof, requiring certain amount of confirmations,
+// returns metadata of the transaction's block
+pub fn process_verify_transaction(main_state: &AccountLoader<MainState>, reversed_txid: &[u8; 32], confirmations: u32, tx_index: u32, reversed_merkle_proof: &[[u8; 32]], commited_header: &CommittedBlockHeader) -> Result<VerifiedBlock> {
+    let current_confirmations = verify_commited_header(main_state, confirmations, commited_header)?;
+
+    let computed_merkle = utils::compute_merkle(reversed_txid, tx_index, reversed_merkle_proof);
+
+    require!(
+        computed_merkle == commited_header.header.merkle_root,
+        RelayErrorCode::MerkleRoot
+    );
+
+    VerifiedBlock::new(commited_header, current_confirmations)
+}
+
+//Processes verification of the transaction block inclusion proof, same as process_verify_transaction, but additionally
+// takes the coinbase transaction with its merkle proof, the coinbase transaction is used to check the block height
+// committed to by the block (BIP34), and the length of its merkle proof (coinbase is always at index 0, so its proof
+// has a node on every level of the tree) to check that the transaction's merkle proof spans the full depth of the tree,
+// returns metadata of the transaction's block
+#[allow(clippy::too_many_arguments)]
+pub fn process_verify_transaction_with_coinbase(main_state: &AccountLoader<MainState>, reversed_txid: &[u8; 32], confirmations: u32, tx_index: u32, reversed_merkle_proof: &[[u8; 32]], coinbase: &CoinbaseProof, commited_header: &CommittedBlockHeader) -> Result<VerifiedBlock> {
+    let current_confirmations = verify_commited_header(main_state, confirmations, commited_header)?;
+
+    let coinbase_tx = utils::verify_coinbase(coinbase, &commited_header.header.merkle_root)?;
+
+    //https://github.com/bitcoin/bips/blob/master/bip-0034.mediawiki
+    if commited_header.blockheight >= consensus::PARAMS.bip34_height {
+        let mut expected_script = Vec::new();
+        btctx::write_script_number(commited_header.blockheight, &mut expected_script);
+        require!(
+            coinbase_tx.inputs[0].script.starts_with(&expected_script),
+            RelayErrorCode::InvalidCoinbaseHeight
+        );
+    }
+
+    //Merkle proof has to have the same depth as the tree, with the tx_index fitting in it
+    let depth = coinbase.reversed_merkle_proof.len();
+    require!(
+        reversed_merkle_proof.len() == depth && (depth >= 32 || tx_index >> depth == 0),
+        RelayErrorCode::InvalidMerkleProofDepth
+    );
+
+    let computed_merkle = utils::compute_merkle(reversed_txid, tx_index, reversed_merkle_proof);
+
+    require!(
+        computed_merkle == commited_header.header.merkle_root,
+        RelayErrorCode::MerkleRoot
+    );
+
+    VerifiedBlock::new(commited_header, current_confirmations)
+}
+
+//Processes verification of the segwit transaction block inclusion proof by its wtxid, requiring certain amount of confirmations,
+// the wtxid merkle proof is verified against the witness commitment (BIP141) in the coinbase transaction,
+// returns metadata of the transaction's block
+#[allow(clippy::too_many_arguments)]
+pub fn process_verify_witness_transaction(main_state: &AccountLoader<MainState>, reversed_wtxid: &[u8; 32], confirmations: u32, wtx_index: u32, reversed_witness_merkle_proof: &[[u8; 32]], coinbase: &CoinbaseProof, witness_reserved_value: &[u8; 32], commited_header: &CommittedBlockHeader) -> Result<VerifiedBlock> {
+    let current_confirmations = verify_commited_header(main_state, confirmations, commited_header)?;
+
+    let coinbase_tx = utils::verify_coinbase(coinbase, &commited_header.header.merkle_root)?;
+    let witness_commitment = btctx::get_witness_commitment(&coinbase_tx).ok_or(RelayErrorCode::InvalidWitnessCommitment)?;
+
+    //Coinbase transaction's wtxid is always 0x00...00, so it can't be proven
+    require!(
+        wtx_index != 0,
+        RelayErrorCode::InvalidWitnessCommitment
+    );
+
+    //Witness merkle tree has the same leaf count as the transaction merkle tree, so the wtxid merkle proof has to have
+    // the same depth as the coinbase merkle proof, with the wtx_index fitting in it
+    let depth = coinbase.reversed_merkle_proof.len();
+    require!(
+        reversed_witness_merkle_proof.len() == depth && (depth >= 32 || wtx_index >> depth == 0),
+        RelayErrorCode::InvalidMerkleProofDepth
+    );
+
+    let witness_root = utils::compute_merkle(reversed_wtxid, wtx_index, reversed_witness_merkle_proof);
+
+    //https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki#commitment-structure
+    let computed_commitment = hash::hash(&hash::hashv(&[&witness_root, witness_reserved_value]).to_bytes()).to_bytes();
+    require!(
+        computed_commitment == witness_commitment,
+        RelayErrorCode::InvalidWitnessCommitment
+    );
+
+    VerifiedBlock::new(commited_header, current_confirmations)
+}
+
+//Processes verification of multiple transactions' block inclusion proofs, requiring certain amount of confirmations
+// for all the blocks, every committed header is only verified once, no matter how many proofs refer to it
+//The number of transactions in the partial merkle trees is unauthenticated, so the tree height it implies is checked
+// against the length of the block's coinbase merkle proof (same as in process_verify_transaction_with_coinbase),
+// the transactions matched by the partial merkle trees have to be exactly the expected ones
+pub fn process_verify_transactions(main_state: &AccountLoader<MainState>, confirmations: u32, commited_headers: &[CommittedBlockHeader], coinbase_proofs: &[CoinbaseProof], proofs: &[BlockTransactionsProof]) -> Result<()> {
+    require!(
+        !proofs.is_empty(),
+        RelayErrorCode::InvalidPartialMerkleTree
+    );
+    require!(
+        coinbase_proofs.len() == commited_headers.len(),
+        RelayErrorCode::InvalidCoinbase
+    );
+
+    let main_state = MainStateRef::load(main_state)?;
+
+    //Main chain might get replaced by the pending deep reorg fork
+    require!(
+        !main_state.is_deep_reorg_pending(),
+        RelayErrorCode::DeepReorgPending
+    );
+
+    for commited_header in commited_headers.iter() {
+        let block_height = commited_header.blockheight;
+
+        require!(
+            block_height <= main_state.block_height && main_state.block_height - block_height + 1 >= confirmations,
+            RelayErrorCode::BlockConfirmations
+        );
+
+        let commit_hash = commited_header.get_commit_hash()?;
+        require!(
+            commit_hash == main_state.get_commitment(block_height),
+            RelayErrorCode::PrevBlockCommitment
+        );
+    }
+
+    for (commited_header, coinbase) in commited_headers.iter().zip(coinbase_proofs.iter()) {
+        utils::verify_coinbase(coinbase, &commited_header.header.merkle_root)?;
+    }
+
+    for proof in proofs.iter() {
+        let commited_header = commited_headers.get(proof.header_index as usize).ok_or(RelayErrorCode::InvalidPartialMerkleTree)?;
+        let coinbase = &coinbase_proofs[proof.header_index as usize];
+
+        //Partial merkle tree has to have the same depth as the block's merkle tree
+        require!(
+            merkleblock::tree_height(proof.total_transactions) as usize == coinbase.reversed_merkle_proof.len(),
+            RelayErrorCode::InvalidMerkleProofDepth
+        );
+
+        let (computed_merkle, matches) = merkleblock::extract_matches(proof.total_transactions, &proof.reversed_hashes, &proof.flags)
+            .ok_or(RelayErrorCode::InvalidPartialMerkleTree)?;
+
+        require!(
+            !matches.is_empty() && matches == proof.reversed_txids,
+            RelayErrorCode::InvalidPartialMerkleTree
+        );
+
+        require!(
+            computed_merkle == commited_header.header.merkle_root,
+            RelayErrorCode::MerkleRoot
+        );
+    }
+
+    Ok(())
+}
+
+//Parses the raw transaction (with witness data stripped), rejecting 64-byte transactions
+pub fn parse_raw_transaction(transaction: &[u8]) -> Result<btctx::BitcoinTransaction<'_>> {
+    //Security against spoofing bitcoin txs as merkle tree nodes
+    // https://blog.rsk.co/ru/noticia/the-design-of-bitcoin-merkle-trees-reduces-the-security-of-spv-clients/
+    require!(
+        transaction.len()!=64,
+        RelayErrorCode::InvalidTransaction
+    );
+
+    let tx = btctx::parse_transaction(transaction).ok_or(RelayErrorCode::InvalidTransaction)?;
+
+    Ok(tx)
+}
+
+//Parses the raw transaction (with witness data stripped), returns its reversed transaction ID
+pub fn get_raw_transaction_txid(transaction: &[u8]) -> Result<[u8; 32]> {
+    parse_raw_transaction(transaction)?;
+
+    Ok(btctx::get_reversed_txid(transaction))
+}
+
+//Checks that the output at vout of the raw transaction pays at least min_value sats to the script with sha256 hash script_hash,
+// returns the output value
+pub fn check_output_payment(transaction: &[u8], vout: u32, script_hash: &[u8; 32], min_value: u64) -> Result<u64> {
+    let tx = parse_raw_transaction(transaction)?;
+
+    let output = tx.outputs.get(vout as usize).ok_or(RelayErrorCode::InvalidVout)?;
+
+    require!(
+        hash::hash(output.script).to_bytes() == *script_hash,
+        RelayErrorCode::InvalidOutputScript
+    );
+
+    require!(
+        output.value >= min_value,
+        RelayErrorCode::InsufficientOutputValue
+    );
+
+    Ok(output.value)
+}
+
+pub struct RawWitnessTransaction {
+    pub reversed_txid: [u8; 32],
+    pub reversed_wtxid: [u8; 32],
+    pub witness: Vec<Vec<u8>> //Witness stack of the selected input
+}
+
+//Parses the raw segwit transaction (including witness data), returns its reversed transaction ID, reversed witness
+// transaction ID and the witness stack of the input at input_index
+pub fn parse_raw_witness_transaction(transaction: &[u8], input_index: u32) -> Result<RawWitnessTransaction> {
+    let witness_tx = btctx::parse_witness_transaction(transaction).ok_or(RelayErrorCode::InvalidTransaction)?;
+
+    let reversed_txid = get_raw_transaction_txid(&witness_tx.stripped)?;
+    let reversed_wtxid = btctx::get_reversed_wtxid(transaction);
+
+    let witness = witness_tx.witnesses.get(input_index as usize).ok_or(RelayErrorCode::InvalidTransaction)?;
+
+    Ok(RawWitnessTransaction {
+        reversed_txid,
+        reversed_wtxid,
+        witness: witness.iter().map(|item| item.to_vec()).collect()
+    })
+}
+
+//Reads the raw transaction of transaction_len bytes from the data account, which has the signer key as its first 32 bytes
+pub fn read_data_account_transaction<'a>(data: &'a [u8], signer: &Signer, transaction_len: u32) -> Result<&'a [u8]> {
+    let data_end = 32 + transaction_len as usize;
+    require!(
+        data.len() >= data_end && data[0..32]==signer.key.to_bytes(),
+        RelayErrorCode::InvalidDataAccount
+    );
+
+    Ok(&data[32..data_end])
+}
@@ -1,16 +1,28 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{
+    prelude::*,
+    system_program
+};
 use instructions::*;
 use events::*;
 use errors::*;
 use structs::*;
-
-mod arrayutils;
+use consensus::PARAMS;
+use state::*;
+use u256::U256;
+
+mod btctx;
+mod mmr;
+mod merkleblock;
+mod consensus;
+mod signet;
 mod utils;
+mod ixs;
 mod instructions;
 mod events;
 mod errors;
-mod structs;
+pub mod structs;
 mod state;
+mod u256;
 
 declare_id!("3KHSHFpEK6bsjg3bqcxQ9qssJYtRCMi2S9TYVe4q6CQc");
 
@@ -26,16 +38,39 @@ pub mod btc_relay {
         block_height: u32,
         chain_work: [u8; 32],
         last_diff_adjustment: u32,
-        prev_block_timestamps: [u32; 10]
+        prev_block_timestamps: [u32; 10],
+        pruning_factor: u32
     ) -> Result<()> {
-        let main_state = &mut ctx.accounts.main_state.load_init()?;
+        require!(
+            (MIN_PRUNING_FACTOR..=MAX_PRUNING_FACTOR).contains(&pruning_factor),
+            RelayErrorCode::PruningFactorOutOfRange
+        );
+
+        //On testnet the initial block mustn't be mined using the minimum-difficulty exception,
+        // as its nBits is used as the difficulty for subsequent blocks
+        require!(
+            !PARAMS.allow_min_difficulty_blocks || utils::is_non_min_difficulty_block(block_height, data.nbits),
+            RelayErrorCode::ErrDiffTarget
+        );
+
+        //When initializing from the genesis block, it has to match the network's genesis block
+        let block_hash = data.get_block_hash()?;
+        require!(
+            block_height != 0 || block_hash == PARAMS.reversed_genesis_block_hash,
+            RelayErrorCode::InvalidGenesisBlock
+        );
+
+        let main_state = &mut MainStateRefMut::load_init(&ctx.accounts.main_state)?;
 
         main_state.last_diff_adjustment = last_diff_adjustment;
         main_state.block_height = block_height;
-        main_state.chain_work = chain_work;
+        main_state.chain_work = U256::from_be_bytes(chain_work);
 
         main_state.fork_counter = 0;
 
+        main_state.mmr_start_height = block_height;
+        main_state.mmr_leaf_count = 0;
+
         let commited_header = CommittedBlockHeader {
             chain_work,
 
@@ -44,16 +79,17 @@ pub mod btc_relay {
             last_diff_adjustment,
             blockheight: block_height,
         
-            prev_block_timestamps
+            prev_block_timestamps,
+
+            #[cfg(any(feature = "bitcoin_testnet", feature = "bitcoin_testnet4"))]
+            last_non_min_nbits: data.nbits
         };
 
         let hash_result = commited_header.get_commit_hash()?;
-        let block_hash = data.get_block_hash()?;
 
-        main_state.block_commitments[0] = hash_result;
-        
         main_state.start_height = block_height;
-        main_state.total_blocks = 1;
+        main_state.total_blocks = 0;
+        main_state.store_block_commitment(block_height, hash_result);
 
         main_state.tip_block_hash = block_hash;
         main_state.tip_commit_hash = hash_result;
@@ -67,311 +103,691 @@ pub mod btc_relay {
         Ok(())
     }
 
-    //Submit new main chain blockheaders
-    pub fn submit_block_headers(ctx: Context<SubmitBlockHeaders>, data: Vec<BlockHeader>, commited_header: CommittedBlockHeader) -> Result<()> {
+    //Submit new main chain blockheaders, blockheaders can be passed in any of the HeadersData encodings (on signet only as
+    // signet blockheaders, accompanied by their coinbase transaction containing the block solution), every blockheader
+    // requires its header topic (PDA derived from the blockhash, used only for indexing) in the remaining accounts,
+    // unless header_topics is false, the remaining accounts are then just the optional fee pool & submitter rewards PDAs
+    pub fn submit_block_headers(ctx: Context<SubmitBlockHeaders>, data: HeadersData, commited_header: CommittedBlockHeader, header_topics: bool) -> Result<()> {
+        ixs::submit::process_submit_block_headers(
+            &ctx.accounts.main_state,
+            &ctx.accounts.signer,
+            ctx.remaining_accounts,
+            ctx.program_id,
+            &data,
+            commited_header,
+            header_topics
+        )
+    }
+
+    //Submit new headers forking the chain at some point in the past,
+    // only allows submission of up to 7 blockheaders, due to Solana tx size limitation
+    pub fn submit_short_fork_headers(ctx: Context<SubmitShortForkHeaders>, data: HeadersData, commited_header: CommittedBlockHeader, header_topics: bool) -> Result<()> {
+        ixs::submit::process_submit_short_fork_headers(
+            &ctx.accounts.main_state,
+            &ctx.accounts.signer,
+            ctx.remaining_accounts,
+            ctx.program_id,
+            &data,
+            commited_header,
+            header_topics
+        )
+    }
+
+    //Submit new headers forking the chain at some point in the past,
+    // this stores the new fork's blockheaders in an intermediary fork PDA,
+    // allowing forks of >7 blocks, as soon as the fork chain's work exceeds
+    // the main chain's work, the main chain is overwritten and fork PDA closed
+    pub fn submit_fork_headers(ctx: Context<SubmitForkHeaders>, data: HeadersData, commited_header: CommittedBlockHeader, fork_id: u64, init: bool, header_topics: bool) -> Result<()> {
+        ixs::submit::process_submit_fork_headers(
+            &ctx.accounts.main_state,
+            &ctx.accounts.fork_state,
+            &ctx.accounts.signer,
+            &ctx.accounts.system_program,
+            ctx.remaining_accounts,
+            ctx.program_id,
+            &data,
+            commited_header,
+            fork_id,
+            init,
+            header_topics
+        )
+    }
+
+    //Submit new headers forking the chain at some point in the past to the shared fork PDA, same as submit_fork_headers,
+    // but the shared fork PDA is not tied to the signer, so any submitter can continue extending the fork,
+    // when the fork overtakes the main chain the PDA's rent is refunded to the fork's contributors,
+    // whose accounts have to be passed in the remaining accounts right after the header topics
+    pub fn submit_shared_fork_headers<'info>(ctx: Context<'_, '_, '_, 'info, SubmitSharedForkHeaders<'info>>, data: HeadersData, commited_header: CommittedBlockHeader, fork_id: u64, init: bool, header_topics: bool) -> Result<()> {
+        ixs::shared_fork::process_submit_shared_fork_headers(
+            &ctx.accounts.main_state,
+            &ctx.accounts.fork_state,
+            &ctx.accounts.signer,
+            &ctx.accounts.system_program,
+            ctx.remaining_accounts,
+            ctx.program_id,
+            &data,
+            commited_header,
+            fork_id,
+            init,
+            header_topics
+        )
+    }
+
+    //Resizes the main state PDA's ring buffer to the larger pruning_factor, migrating the legacy main state PDA to the current
+    // layout, might need to be called multiple times, as the account can only grow by 10KB per instruction
+    pub fn resize_main_state(ctx: Context<ResizeMainState>, pruning_factor: u32) -> Result<()> {
+        ixs::resize::process_resize_main_state(
+            &ctx.accounts.main_state,
+            &ctx.accounts.signer,
+            &ctx.accounts.program_data,
+            &ctx.accounts.system_program,
+            pruning_factor
+        )
+    }
+
+    //Used to close the fork PDA, the fork stops being the pending deep reorg fork
+    pub fn close_fork_account(ctx: Context<CloseForkAccount>, fork_id: u64) -> Result<()> {
+        MainStateRefMut::load_mut(&ctx.accounts.main_state)?.clear_pending_fork(fork_id);
+        Ok(())
+    }
+
+    //Used by the creator to close the shared fork PDA, abandoning the fork, the PDA's rent is refunded to the fork's
+    // contributors, whose accounts have to be passed in the remaining accounts
+    pub fn close_shared_fork_account<'info>(ctx: Context<'_, '_, '_, 'info, CloseSharedForkAccount<'info>>, fork_id: u64) -> Result<()> {
+        ixs::shared_fork::process_close_shared_fork_account(
+            &ctx.accounts.main_state,
+            &ctx.accounts.fork_state,
+            &ctx.accounts.signer,
+            ctx.remaining_accounts,
+            fork_id
+        )
+    }
+
+    //Permissionless applying of the pending deep reorg fork, after it was pending for the challenge delay,
+    // tip_header is the fork's tip, fork PDA is closed and its rent refunded to the fork's creator
+    pub fn apply_pending_fork(ctx: Context<ApplyPendingFork>, fork_id: u64, tip_header: CommittedBlockHeader) -> Result<()> {
+        ixs::submit::process_apply_pending_fork(
+            &ctx.accounts.main_state,
+            &ctx.accounts.fork_state,
+            &ctx.accounts.creator,
+            fork_id,
+            tip_header
+        )
+    }
+
+    //Permissionless applying of the pending deep reorg shared fork, same as apply_pending_fork, the PDA's rent is refunded
+    // to the fork's contributors, whose accounts have to be passed in the remaining accounts
+    pub fn apply_pending_shared_fork<'info>(ctx: Context<'_, '_, '_, 'info, ApplyPendingSharedFork<'info>>, fork_id: u64, tip_header: CommittedBlockHeader) -> Result<()> {
+        ixs::shared_fork::process_apply_pending_shared_fork(
+            &ctx.accounts.main_state,
+            &ctx.accounts.fork_state,
+            &ctx.accounts.signer,
+            ctx.remaining_accounts,
+            fork_id,
+            tip_header
+        )
+    }
+
+    //Permissionless closing of a fork PDA, which can no longer become the main chain - its start height is already more than
+    // pruning_factor blocks behind the main chain's tip, or its tip is already part of the main chain, the signer gets
+    // a share of the reclaimed rent as a bounty, rest is refunded to the fork's creator
+    pub fn close_stale_fork_account(ctx: Context<CloseStaleForkAccount>, fork_id: u64) -> Result<()> {
+        ixs::cleanup::process_close_stale_fork_account(
+            &ctx.accounts.main_state,
+            &ctx.accounts.fork_state,
+            &ctx.accounts.signer,
+            &ctx.accounts.creator,
+            fork_id
+        )
+    }
+
+    //Permissionless closing of a shared fork PDA, which can no longer become the main chain, same as close_stale_fork_account,
+    // rest of the reclaimed rent is refunded to the fork's contributors, whose accounts have to be passed in the remaining accounts
+    pub fn close_stale_shared_fork_account<'info>(ctx: Context<'_, '_, '_, 'info, CloseStaleSharedForkAccount<'info>>, fork_id: u64) -> Result<()> {
+        ixs::cleanup::process_close_stale_shared_fork_account(
+            &ctx.accounts.main_state,
+            &ctx.accounts.fork_state,
+            &ctx.accounts.signer,
+            ctx.remaining_accounts,
+            fork_id
+        )
+    }
+
+    //Initializes the fee pool PDA, paying out header_reward lamports for every blockheader accepted into the main chain,
+    // only callable by the program's upgrade authority, which becomes the fee pool's authority, allowed to change the header reward
+    // & withdraw from the fee pool
+    pub fn init_fee_pool(ctx: Context<InitFeePool>, header_reward: u64) -> Result<()> {
         require!(
-            !data.is_empty(),
-            RelayErrorCode::NoHeaders
+            ctx.accounts.program_data.upgrade_authority_address == Some(ctx.accounts.signer.key()),
+            RelayErrorCode::InvalidUpgradeAuthority
         );
-        
+
+        let fee_pool = &mut ctx.accounts.fee_pool.load_init()?;
+
+        fee_pool.authority = ctx.accounts.signer.key();
+        fee_pool.header_reward = header_reward;
+
+        Ok(())
+    }
+
+    //Changes the reward paid out by the fee pool for every accepted blockheader, only callable by the fee pool's authority
+    pub fn set_header_reward(ctx: Context<SetHeaderReward>, header_reward: u64) -> Result<()> {
+        let fee_pool = &mut ctx.accounts.fee_pool.load_mut()?;
+
         require!(
-            data.len() == ctx.remaining_accounts.len(),
-            RelayErrorCode::InvalidRemainingAccounts
+            fee_pool.authority == ctx.accounts.signer.key(),
+            RelayErrorCode::InvalidFeePoolAuthority
         );
 
-        //Verify commited header was indeed committed
-        let commit_hash = commited_header.get_commit_hash()?;
-        let main_state = &mut ctx.accounts.main_state.load_mut()?;
-        let main_state_tip = main_state.get_commitment(main_state.block_height);
+        fee_pool.header_reward = header_reward;
+
+        Ok(())
+    }
+
+    //Tops up the fee pool, can be called by anyone, e.g. as a CPI by the programs relying on the relay
+    pub fn fund_fee_pool(ctx: Context<FundFeePool>, amount: u64) -> Result<()> {
+        let transfer_lamports_instruction = system_program::Transfer {
+            from: ctx.accounts.signer.to_account_info(),
+            to: ctx.accounts.fee_pool.to_account_info()
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), transfer_lamports_instruction);
+        system_program::transfer(cpi_ctx, amount)
+    }
+
+    //Withdraws lamports from the fee pool to its authority, only the balance above the rent exempt minimum can be withdrawn
+    pub fn withdraw_fee_pool(ctx: Context<WithdrawFeePool>, amount: u64) -> Result<()> {
         require!(
-            commit_hash == main_state_tip,
-            RelayErrorCode::PrevBlockCommitment
+            ctx.accounts.fee_pool.load()?.authority == ctx.accounts.signer.key(),
+            RelayErrorCode::InvalidFeePoolAuthority
         );
 
-        let mut last_commited_header = commited_header;
-        let mut last_block_hash: [u8; 32] = commited_header.header.get_block_hash()?;
-        let mut block_height = main_state.block_height;
-        let mut block_commit_hash: [u8; 32] = [0; 32];
+        let fee_pool_info = ctx.accounts.fee_pool.to_account_info();
+        let available_lamports = fee_pool_info.lamports().saturating_sub(Rent::get()?.minimum_balance(fee_pool_info.data_len()));
+        require!(
+            amount <= available_lamports,
+            RelayErrorCode::InsufficientFeePoolBalance
+        );
+
+        **fee_pool_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.signer.to_account_info().try_borrow_mut_lamports()? += amount;
+
+        Ok(())
+    }
+
+    //Initializes the signer's submitter rewards PDA, which along with the fee pool PDA can be passed in the remaining accounts
+    // of the header submission instructions (right after the header topics), to get rewarded for the accepted blockheaders
+    pub fn init_submitter_rewards(ctx: Context<InitSubmitterRewards>) -> Result<()> {
+        let submitter_rewards = &mut ctx.accounts.submitter_rewards.load_init()?;
+
+        submitter_rewards.submitter = ctx.accounts.signer.key();
+
+        Ok(())
+    }
+
+    //Verifies transaction block inclusion proof, requiring certain amount of confirmations
+    //Can be called as a CPI or a standalone instruction, that gets executed
+    // before the instructions that depend on transaction verification
+    //Metadata of the transaction's block is set as the return data, CPI callers can decode it with VerifiedBlock::from_return_data
+    pub fn verify_transaction(ctx: Context<VerifyTransaction>, reversed_txid: [u8; 32], confirmations: u32, tx_index: u32, reversed_merkle_proof: Vec<[u8; 32]>, commited_header: CommittedBlockHeader) -> Result<VerifiedBlock> {
+        #[cfg(feature = "mocked")]
+        {
+            return VerifiedBlock::new(&commited_header, confirmations);
+        }
+        
+        #[cfg(not(feature = "mocked"))]
+        {
+            ixs::verify::process_verify_transaction(
+                &ctx.accounts.main_state,
+                &reversed_txid,
+                confirmations,
+                tx_index,
+                &reversed_merkle_proof,
+                &commited_header
+            )
+        }
+    }
+
+    //Verifies transaction block inclusion proof, same as verify_transaction, but takes the raw transaction
+    // (with witness data stripped) instead of the transaction ID, which is computed on-chain
+    //Can be called as a CPI or a standalone instruction, same as verify_transaction
+    pub fn verify_raw_transaction(ctx: Context<VerifyTransaction>, transaction: Vec<u8>, confirmations: u32, tx_index: u32, reversed_merkle_proof: Vec<[u8; 32]>, commited_header: CommittedBlockHeader) -> Result<VerifiedBlock> {
+        let reversed_txid = ixs::verify::get_raw_transaction_txid(&transaction)?;
+
+        #[cfg(feature = "mocked")]
+        {
+            return VerifiedBlock::new(&commited_header, confirmations);
+        }
+
+        #[cfg(not(feature = "mocked"))]
+        {
+            ixs::verify::process_verify_transaction(
+                &ctx.accounts.main_state,
+                &reversed_txid,
+                confirmations,
+                tx_index,
+                &reversed_merkle_proof,
+                &commited_header
+            )
+        }
+    }
+
+    //Verifies transaction block inclusion proof, same as verify_raw_transaction, but the raw transaction
+    // of transaction_len bytes is read from the data account, right after the signer key in its first 32 bytes
+    //Can be called as a CPI or a standalone instruction, same as verify_transaction
+    pub fn verify_raw_transaction_data(ctx: Context<VerifyRawTransactionData>, transaction_len: u32, confirmations: u32, tx_index: u32, reversed_merkle_proof: Vec<[u8; 32]>, commited_header: CommittedBlockHeader) -> Result<VerifiedBlock> {
+        let acc_data = ctx.accounts.data.try_borrow_data()?;
+        let transaction = ixs::verify::read_data_account_transaction(&acc_data, &ctx.accounts.signer, transaction_len)?;
+        let reversed_txid = ixs::verify::get_raw_transaction_txid(transaction)?;
+
+        #[cfg(feature = "mocked")]
+        {
+            return VerifiedBlock::new(&commited_header, confirmations);
+        }
+
+        #[cfg(not(feature = "mocked"))]
+        {
+            ixs::verify::process_verify_transaction(
+                &ctx.accounts.main_state,
+                &reversed_txid,
+                confirmations,
+                tx_index,
+                &reversed_merkle_proof,
+                &commited_header
+            )
+        }
+    }
+
+    //Verifies transaction block inclusion proof, same as verify_transaction, but additionally takes the coinbase transaction
+    // (with witness data stripped) and its merkle proof, checking the block height committed to in the coinbase (BIP34),
+    // and that the transaction's merkle proof length matches the block's merkle tree depth
+    //Can be called as a CPI or a standalone instruction, same as verify_transaction
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_transaction_with_coinbase(ctx: Context<VerifyTransaction>, reversed_txid: [u8; 32], confirmations: u32, tx_index: u32, reversed_merkle_proof: Vec<[u8; 32]>, coinbase: CoinbaseProof, commited_header: CommittedBlockHeader) -> Result<VerifiedBlock> {
+        #[cfg(feature = "mocked")]
+        {
+            return VerifiedBlock::new(&commited_header, confirmations);
+        }
+
+        #[cfg(not(feature = "mocked"))]
+        {
+            ixs::verify::process_verify_transaction_with_coinbase(
+                &ctx.accounts.main_state,
+                &reversed_txid,
+                confirmations,
+                tx_index,
+                &reversed_merkle_proof,
+                &coinbase,
+                &commited_header
+            )
+        }
+    }
+
+    //Verifies segwit transaction block inclusion proof by its witness transaction ID (wtxid), requiring certain amount
+    // of confirmations, this proves the transaction's witness data, the coinbase transaction (with witness data stripped)
+    // is proven against the block's merkle root, and the wtxid merkle proof against the coinbase's witness commitment (BIP141),
+    // witness_reserved_value is the coinbase input's witness
+    //Can be called as a CPI or a standalone instruction, same as verify_transaction
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_witness_transaction(ctx: Context<VerifyTransaction>, reversed_wtxid: [u8; 32], confirmations: u32, wtx_index: u32, reversed_witness_merkle_proof: Vec<[u8; 32]>, coinbase: CoinbaseProof, witness_reserved_value: [u8; 32], commited_header: CommittedBlockHeader) -> Result<VerifiedBlock> {
+        #[cfg(feature = "mocked")]
+        {
+            return VerifiedBlock::new(&commited_header, confirmations);
+        }
+
+        #[cfg(not(feature = "mocked"))]
+        {
+            ixs::verify::process_verify_witness_transaction(
+                &ctx.accounts.main_state,
+                &reversed_wtxid,
+                confirmations,
+                wtx_index,
+                &reversed_witness_merkle_proof,
+                &coinbase,
+                &witness_reserved_value,
+                &commited_header
+            )
+        }
+    }
+
+    //Verifies segwit transaction block inclusion proof, same as verify_witness_transaction, but takes the raw transaction
+    // (including witness data) instead of the wtxid, the txid & wtxid are computed on-chain
+    //Verified witness stack of the input at input_index is set as the return data (along with the block metadata & txid),
+    // CPI callers can decode it with VerifiedWitness::from_return_data, the return data is limited to 1024 bytes, so this
+    // only works for inputs with small witnesses
+    //Can be called as a CPI or a standalone instruction, same as verify_transaction
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_raw_witness_transaction(ctx: Context<VerifyTransaction>, transaction: Vec<u8>, input_index: u32, confirmations: u32, wtx_index: u32, reversed_witness_merkle_proof: Vec<[u8; 32]>, coinbase: CoinbaseProof, witness_reserved_value: [u8; 32], commited_header: CommittedBlockHeader) -> Result<VerifiedWitness> {
+        let witness_tx = ixs::verify::parse_raw_witness_transaction(&transaction, input_index)?;
+
+        #[cfg(feature = "mocked")]
+        let block = VerifiedBlock::new(&commited_header, confirmations)?;
+
+        #[cfg(not(feature = "mocked"))]
+        let block = ixs::verify::process_verify_witness_transaction(
+            &ctx.accounts.main_state,
+            &witness_tx.reversed_wtxid,
+            confirmations,
+            wtx_index,
+            &reversed_witness_merkle_proof,
+            &coinbase,
+            &witness_reserved_value,
+            &commited_header
+        )?;
+
+        Ok(VerifiedWitness {
+            block,
+            reversed_txid: witness_tx.reversed_txid,
+            reversed_wtxid: witness_tx.reversed_wtxid,
+            input_index,
+            witness: witness_tx.witness
+        })
+    }
+
+    //Verifies block inclusion of multiple transactions in multiple blocks, requiring certain amount of confirmations
+    // for all the blocks, transactions of every block are proven with a single partial merkle tree (as in bitcoin's
+    // merkleblock message), referring to the block's committed header by its index in commited_headers
+    //Every committed header is accompanied by its block's coinbase transaction proof (at the same index in coinbase_proofs),
+    // binding the depth of the partial merkle trees to the depth of the block's merkle tree
+    //Can be called as a CPI or a standalone instruction, same as verify_transaction
+    pub fn verify_transactions(ctx: Context<VerifyTransaction>, confirmations: u32, commited_headers: Vec<CommittedBlockHeader>, coinbase_proofs: Vec<CoinbaseProof>, proofs: Vec<BlockTransactionsProof>) -> Result<()> {
+        #[cfg(feature = "mocked")]
+        {
+            return Ok(());
+        }
+
+        #[cfg(not(feature = "mocked"))]
+        {
+            ixs::verify::process_verify_transactions(
+                &ctx.accounts.main_state,
+                confirmations,
+                &commited_headers,
+                &coinbase_proofs,
+                &proofs
+            )
+        }
+    }
 
-        for (block_cnt, header) in data.iter().enumerate() {
-            //Prev block hash matches
+    //Verifies that the raw transaction (with witness data stripped) pays at least min_value sats in its output at vout
+    // to the script with sha256 hash script_hash, along with its block inclusion proof, same as verify_raw_transaction
+    //Matched output is set as the return data, CPI callers can decode it with VerifiedOutput::from_return_data
+    //Can be called as a CPI or a standalone instruction, same as verify_transaction
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_output_payment(ctx: Context<VerifyTransaction>, transaction: Vec<u8>, vout: u32, script_hash: [u8; 32], min_value: u64, confirmations: u32, tx_index: u32, reversed_merkle_proof: Vec<[u8; 32]>, commited_header: CommittedBlockHeader) -> Result<VerifiedOutput> {
+        let value = ixs::verify::check_output_payment(&transaction, vout, &script_hash, min_value)?;
+        let reversed_txid = btctx::get_reversed_txid(&transaction);
+
+        #[cfg(feature = "mocked")]
+        let block = VerifiedBlock::new(&commited_header, confirmations)?;
+
+        #[cfg(not(feature = "mocked"))]
+        let block = ixs::verify::process_verify_transaction(
+            &ctx.accounts.main_state,
+            &reversed_txid,
+            confirmations,
+            tx_index,
+            &reversed_merkle_proof,
+            &commited_header
+        )?;
+
+        Ok(VerifiedOutput {
+            block,
+            reversed_txid,
+            vout,
+            value,
+            script_hash
+        })
+    }
+
+    //Verifies transaction block inclusion proof for blocks already pruned from the ring buffer,
+    // block commitment is proven to be included in the MMR accumulator with the mmr_proof
+    //Can be called as a CPI or a standalone instruction, same as verify_transaction
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_transaction_historic(ctx: Context<VerifyTransaction>, reversed_txid: [u8; 32], confirmations: u32, tx_index: u32, reversed_merkle_proof: Vec<[u8; 32]>, commited_header: CommittedBlockHeader, mmr_proof: Vec<[u8; 32]>) -> Result<()> {
+        #[cfg(feature = "mocked")]
+        {
+            return Ok(());
+        }
+        
+        #[cfg(not(feature = "mocked"))]
+        {
+            let block_height = commited_header.blockheight;
+
+            let main_state = MainStateRef::load(&ctx.accounts.main_state)?;
+
+            //Main chain might get replaced by the pending deep reorg fork
             require!(
-                last_block_hash == header.reversed_prev_blockhash,
-                RelayErrorCode::PrevBlock
+                !main_state.is_deep_reorg_pending(),
+                RelayErrorCode::DeepReorgPending
             );
 
-            block_height+=1;
-
-            last_block_hash = utils::verify_header(
-                header,
-                &mut last_commited_header,
-                &ctx.remaining_accounts[block_cnt],
-                &ctx.accounts.signer,
-                ctx.program_id
-            )?;
-            
-            //Compute commit hash
-            block_commit_hash = last_commited_header.get_commit_hash()?;
-
-            //Store and emit
-            main_state.store_block_commitment(block_height, block_commit_hash);
-            emit!(StoreHeader {
-                block_hash: last_block_hash,
-                commit_hash: block_commit_hash,
-                header: last_commited_header
-            });
-        }
+            require!(
+                block_height <= main_state.block_height && main_state.block_height - block_height + 1 >= confirmations,
+                RelayErrorCode::BlockConfirmations
+            );
 
-        //Update globals
-        main_state.last_diff_adjustment = last_commited_header.last_diff_adjustment;
-        main_state.block_height = block_height;
-        main_state.chain_work = last_commited_header.chain_work;
-        main_state.tip_commit_hash = block_commit_hash;
-        main_state.tip_block_hash = last_block_hash;
+            let commit_hash = commited_header.get_commit_hash()?;
+            require!(
+                main_state.verify_historic_commitment(block_height, commit_hash, &mmr_proof),
+                RelayErrorCode::InvalidMmrProof
+            );
 
-        Ok(())
+            let computed_merkle = utils::compute_merkle(&reversed_txid, tx_index, &reversed_merkle_proof);
+
+            require!(
+                computed_merkle == commited_header.header.merkle_root,
+                RelayErrorCode::MerkleRoot
+            );
+
+            Ok(())
+        }
     }
 
-    //Submit new headers forking the chain at some point in the past,
-    // only allows submission of up to 7 blockheaders, due to Solana tx size limitation
-    pub fn submit_short_fork_headers(ctx: Context<SubmitShortForkHeaders>, data: Vec<BlockHeader>, commited_header: CommittedBlockHeader) -> Result<()> {
-        require!(
-            !data.is_empty(),
-            RelayErrorCode::NoHeaders
-        );
+    //Verifies transaction block inclusion proof for blocks already pruned from the ring buffer, by linking back
+    // from the commited header (still stored in the ring buffer) through the chain of raw blockheaders
+    // stored in the data account, ordered from the oldest, the transaction's block being the first one
+    //Can be called as a CPI or a standalone instruction, same as verify_transaction
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_transaction_linked(ctx: Context<VerifyTransactionLinked>, reversed_txid: [u8; 32], confirmations: u32, tx_index: u32, reversed_merkle_proof: Vec<[u8; 32]>, commited_header: CommittedBlockHeader, header_count: u32) -> Result<()> {
+        #[cfg(feature = "mocked")]
+        {
+            return Ok(());
+        }
         
-        require!(
-            data.len() == ctx.remaining_accounts.len(),
-            RelayErrorCode::InvalidRemainingAccounts
-        );
+        #[cfg(not(feature = "mocked"))]
+        {
+            require!(
+                header_count <= commited_header.blockheight,
+                RelayErrorCode::InvalidBlockheight
+            );
+            let block_height = commited_header.blockheight - header_count;
+
+            let main_state = MainStateRef::load(&ctx.accounts.main_state)?;
+
+            //Main chain might get replaced by the pending deep reorg fork
+            require!(
+                !main_state.is_deep_reorg_pending(),
+                RelayErrorCode::DeepReorgPending
+            );
 
-        //Verify commited header was indeed committed
-        let commit_hash = commited_header.get_commit_hash()?;
+            require!(
+                block_height <= main_state.block_height && main_state.block_height - block_height + 1 >= confirmations,
+                RelayErrorCode::BlockConfirmations
+            );
 
-        let main_state = &mut ctx.accounts.main_state.load_mut()?;
+            let commit_hash = commited_header.get_commit_hash()?;
+            require!(
+                commit_hash == main_state.get_commitment(commited_header.blockheight),
+                RelayErrorCode::PrevBlockCommitment
+            );
 
-        require!(
-            commit_hash == main_state.get_commitment(commited_header.blockheight),
-            RelayErrorCode::PrevBlockCommitment
-        );
+            //Check signer key matches
+            let acc_data = ctx.accounts.data.try_borrow_data()?;
+            let data_end = 32 + (header_count as usize)*utils::BLOCK_HEADER_SIZE;
+            require!(
+                acc_data.len() >= data_end && acc_data[0..32]==ctx.accounts.signer.key.to_bytes(),
+                RelayErrorCode::InvalidDataAccount
+            );
 
-        let fork_id = main_state.fork_counter;
-        let mut last_commited_header = commited_header;
-        let mut last_block_hash: [u8; 32] = commited_header.header.get_block_hash()?;
-        let mut block_height = commited_header.blockheight;
+            let header = utils::verify_header_chain(&acc_data[32..data_end], commited_header.header.reversed_prev_blockhash)?;
 
-        let mut block_commit_hash: [u8; 32] = [0; 32];
+            let computed_merkle = utils::compute_merkle(&reversed_txid, tx_index, &reversed_merkle_proof);
 
-        for (block_cnt, header) in data.iter().enumerate() {
-            //Prev block hash matches
             require!(
-                last_block_hash == header.reversed_prev_blockhash,
-                RelayErrorCode::PrevBlock
+                computed_merkle == header.merkle_root,
+                RelayErrorCode::MerkleRoot
             );
 
-            block_height+=1;
-
-            last_block_hash = utils::verify_header(
-                header,
-                &mut last_commited_header,
-                &ctx.remaining_accounts[block_cnt],
-                &ctx.accounts.signer,
-                ctx.program_id
-            )?;
-            
-            //Compute commit hash
-            block_commit_hash = last_commited_header.get_commit_hash()?;
-
-            //Store and emit
-            main_state.store_block_commitment(block_height, block_commit_hash);
-            emit!(StoreFork {
-                fork_id,
-                block_hash: last_block_hash,
-                commit_hash: block_commit_hash,
-                header: last_commited_header
-            });
+            Ok(())
         }
+    }
 
-        //Verify if fork chain's work exceeded main chain's work
+    //Initializes the data account, by writting signer's key to it
+    pub fn init_data(ctx: Context<InitData>) -> Result<()> {
         require!(
-            arrayutils::gt_arr(last_commited_header.chain_work, main_state.chain_work),
-            RelayErrorCode::ForkTooShort
+            ctx.accounts.data.is_writable,
+            RelayErrorCode::InvalidAccountWritability
         );
 
-        //Update globals
-        main_state.last_diff_adjustment = last_commited_header.last_diff_adjustment;
-        main_state.block_height = block_height;
-        main_state.chain_work = last_commited_header.chain_work;
-        main_state.tip_commit_hash = block_commit_hash;
-        main_state.tip_block_hash = last_block_hash;
-        main_state.fork_counter = fork_id+1;
+        //Account has to be fresh (zeroed) or already owned by the signer, so an existing data account
+        // of a different signer cannot be taken over
+        let mut acc_data = ctx.accounts.data.try_borrow_mut_data()?;
+        let opt_owner = acc_data.get_mut(0..32);
+        require!(
+            opt_owner.is_some(),
+            RelayErrorCode::InvalidDataAccount
+        );
+        let owner = opt_owner.unwrap();
+        let signer_key = ctx.accounts.signer.key.to_bytes();
+        require!(
+            owner.iter().all(|byte| *byte==0) || *owner==signer_key,
+            RelayErrorCode::InvalidDataAccount
+        );
+        owner.copy_from_slice(&signer_key);
 
         Ok(())
     }
 
-    //Submit new headers forking the chain at some point in the past,
-    // this stores the new fork's blockheaders in an intermediary fork PDA,
-    // allowing forks of >7 blocks, as soon as the fork chain's work exceeds
-    // the main chain's work, the main chain is overwritten and fork PDA closed
-    pub fn submit_fork_headers(ctx: Context<SubmitForkHeaders>, data: Vec<BlockHeader>, commited_header: CommittedBlockHeader, fork_id: u64, init: bool) -> Result<()> {
+    //Initializes chunk of data to the data account
+    pub fn write_data(ctx: Context<WriteData>, start: u32, data: Vec<u8>) -> Result<()> {
         require!(
-            !data.is_empty(),
-            RelayErrorCode::NoHeaders
+            ctx.accounts.data.is_writable,
+            RelayErrorCode::InvalidAccountWritability
         );
-        
+
+        //Check signer key matches
+        let mut acc_data = ctx.accounts.data.try_borrow_mut_data()?;
         require!(
-            data.len() == ctx.remaining_accounts.len(),
-            RelayErrorCode::InvalidRemainingAccounts
+            acc_data.get(0..32)==Some(&ctx.accounts.signer.key.to_bytes()[..]),
+            RelayErrorCode::InvalidDataAccount
         );
 
-        let mut close = false;
+        //Written range has to fit in the account
+        let opt_end = (start as usize).checked_add(32).and_then(|offset| offset.checked_add(data.len()));
+        require!(
+            opt_end.is_some(),
+            RelayErrorCode::InvalidDataAccount
+        );
+        let opt_chunk = acc_data.get_mut(((start as usize)+32)..opt_end.unwrap());
+        require!(
+            opt_chunk.is_some(),
+            RelayErrorCode::InvalidDataAccount
+        );
+        opt_chunk.unwrap().copy_from_slice(&data);
 
-        {
+        Ok(())
+    }
 
-            let load_res = if init {
-                ctx.accounts.fork_state.load_init()
-            } else {
-                ctx.accounts.fork_state.load_mut()
-            };
+    //Closes data account
+    pub fn close_data(ctx: Context<CloseData>) -> Result<()> {
+        require!(
+            ctx.accounts.data.is_writable,
+            RelayErrorCode::InvalidAccountWritability
+        );
 
-            let fork_state = &mut load_res?;
+        //Check signer key matches
+        let acc_data = ctx.accounts.data.try_borrow_data()?;
+        require!(
+            acc_data.get(0..32)==Some(&ctx.accounts.signer.key.to_bytes()[..]),
+            RelayErrorCode::InvalidDataAccount
+        );
 
-            //Only yet uninitialized PDA can be initialized
-            require!(
-                init == (fork_state.initialized==0),
-                RelayErrorCode::ErrInit
-            );
+        let mut acc_balance = ctx.accounts.data.try_borrow_mut_lamports()?;
+        let balance: u64 = **acc_balance;
+        **acc_balance = 0;
 
-            let main_state = &mut ctx.accounts.main_state.load_mut()?;
+        let mut signer_balance = ctx.accounts.signer.try_borrow_mut_lamports()?;
+        **signer_balance += balance;
 
-            let commit_hash = commited_header.get_commit_hash()?;
+        Ok(())
+    }
 
-            let mut block_height = commited_header.blockheight;
-
-            if fork_state.initialized==0 {
-                //Has to use new fork_id from the fork_counter
-                require!(
-                    main_state.fork_counter == fork_id,
-                    RelayErrorCode::InvalidForkId
-                );
-
-                main_state.fork_counter = fork_id+1;
-
-                //Verify commited header was indeed committed,
-                // the latest common ancestor block, right before the fork occurred
-                require!(
-                    commit_hash == main_state.get_commitment(commited_header.blockheight),
-                    RelayErrorCode::PrevBlockCommitment
-                );
-
-                fork_state.initialized = 1;
-                fork_state.start_height = block_height;
-            } else {
-                //Verify commited header was indeed committed in the fork state
-                require!(
-                    commit_hash == fork_state.tip_commit_hash,
-                    RelayErrorCode::PrevBlockCommitment
-                );
-            }
-
-            let mut last_commited_header = commited_header;
-            let mut last_block_hash: [u8; 32] = commited_header.header.get_block_hash()?;
-
-            let mut block_commit_hash: [u8; 32] = [0; 32];
-
-            for (block_cnt, header) in data.iter().enumerate() {
-                //Prev block hash matches
-                require!(
-                    last_block_hash == header.reversed_prev_blockhash,
-                    RelayErrorCode::PrevBlock
-                );
-
-                block_height+=1;
-
-                last_block_hash = utils::verify_header(header, &mut last_commited_header, &ctx.remaining_accounts[block_cnt], &ctx.accounts.signer, ctx.program_id)?;
-                
-                //Compute commit hash
-                block_commit_hash = last_commited_header.get_commit_hash()?;
-
-                //Store and emit
-                fork_state.store_block_commitment(block_commit_hash);
-                emit!(StoreFork {
-                    fork_id,
-                    block_hash: last_block_hash,
-                    commit_hash: block_commit_hash,
-                    header: last_commited_header
-                });
-            }
-
-            if arrayutils::gt_arr(last_commited_header.chain_work, main_state.chain_work) {
-                //Successful fork, fork's work exceeded main chain's work
-
-                msg!("Successful fork...");
-
-                //Overwrite block commitments in main chain
-                let start_height = fork_state.start_height;
-                for i in 0..fork_state.length {
-                    main_state.store_block_commitment(start_height+1+i, fork_state.block_commitments[i as usize]);
-                }
-
-                msg!("Commitments stored...");
-
-                //Update main state with fork's state
-                main_state.last_diff_adjustment = last_commited_header.last_diff_adjustment;
-                main_state.block_height = block_height;
-                main_state.chain_work = last_commited_header.chain_work;
-                main_state.tip_commit_hash = block_commit_hash;
-                main_state.tip_block_hash = last_block_hash;
-
-                msg!("Main state updated");
-
-                //Close the fork PDA
-                close = true;
-
-                emit!(ChainReorg {
-                    fork_id,
-                    start_height,
-                    tip_block_hash: last_block_hash,
-                    tip_commit_hash: block_commit_hash
-                });
-            } else {
-                //Fork still needs to be appended
-                fork_state.tip_block_hash = last_block_hash;
-                fork_state.tip_commit_hash = block_commit_hash;
-            }
+    //Verifies that the main chain's tip is not stale, the tip blockheader's timestamp can be at most max_tip_age seconds
+    // older than the current Solana clock, commited_header has to be the main chain's tip
+    //Can be called as a CPI or a standalone instruction, that gets executed
+    // before the instructions that depend on the relay being live
+    pub fn verify_liveness(ctx: Context<VerifyLiveness>, max_tip_age: u32, commited_header: CommittedBlockHeader) -> Result<()> {
+        #[cfg(feature = "mocked")]
+        {
+            return Ok(());
         }
 
-        if close {
-            ctx.accounts.fork_state.close(ctx.accounts.signer.to_account_info())?;
-            msg!("Account closed");
-        }
+        #[cfg(not(feature = "mocked"))]
+        {
+            let main_state = MainStateRef::load(&ctx.accounts.main_state)?;
 
-        Ok(())
-    }
+            //Main chain might get replaced by the pending deep reorg fork
+            require!(
+                !main_state.is_deep_reorg_pending(),
+                RelayErrorCode::DeepReorgPending
+            );
 
-    //Used to close the fork PDA
-    pub fn close_fork_account(_ctx: Context<CloseForkAccount>, _fork_id: u64) -> Result<()> {
-        Ok(())
+            let commit_hash = commited_header.get_commit_hash()?;
+            require!(
+                commit_hash == main_state.tip_commit_hash,
+                RelayErrorCode::PrevBlockCommitment
+            );
+
+            let timestamp = Clock::get()?.unix_timestamp;
+            require!(
+                timestamp <= commited_header.header.timestamp as i64 + max_tip_age as i64,
+                RelayErrorCode::RelayStale
+            );
+
+            Ok(())
+        }
     }
 
-    //Verifies transaction block inclusion proof, requiring certain amount of confirmations
-    //Can be called as a CPI or a standalone instruction, that gets executed
-    // before the instructions that depend on transaction verification
-    pub fn verify_transaction(ctx: Context<VerifyTransaction>, reversed_txid: [u8; 32], confirmations: u32, tx_index: u32, reversed_merkle_proof: Vec<[u8; 32]>, commited_header: CommittedBlockHeader) -> Result<()> {
+    //Verifies median time past (BIP113) of the commited header, which has to be stored in the main chain's ring buffer
+    //Supports the same operators as block_height
+    // 0 - median time past has to be < value
+    // 1 - median time past has to be <= value
+    // 2 - median time past has to be > value
+    // 3 - median time past has to be >= value
+    // 4 - median time past has to be == value
+    //As median time past never decreases, any main chain block proves operators 2 & 3 for the tip too,
+    // for the other operators the commited header has to be the tip to prove the tip's median time past
+    //This can be called a standalone instruction, that gets executed
+    // before the instructions that depend on bitcoin relay having a specific median time past
+    pub fn verify_median_time_past(ctx: Context<VerifyMedianTimePast>, value: u32, operation: u32, commited_header: CommittedBlockHeader) -> Result<()> {
         #[cfg(feature = "mocked")]
         {
             return Ok(());
         }
-        
+
         #[cfg(not(feature = "mocked"))]
         {
-            let block_height = commited_header.blockheight;
+            let main_state = MainStateRef::load(&ctx.accounts.main_state)?;
 
-            let main_state = ctx.accounts.main_state.load()?;
+            //Main chain might get replaced by the pending deep reorg fork
+            require!(
+                !main_state.is_deep_reorg_pending(),
+                RelayErrorCode::DeepReorgPending
+            );
 
+            let block_height = commited_header.blockheight;
             require!(
-                main_state.block_height - block_height + 1 >= confirmations,
-                RelayErrorCode::BlockConfirmations
+                block_height <= main_state.block_height,
+                RelayErrorCode::InvalidBlockheight
             );
 
             let commit_hash = commited_header.get_commit_hash()?;
@@ -380,11 +796,18 @@ pub mod btc_relay {
                 RelayErrorCode::PrevBlockCommitment
             );
 
-            let computed_merkle = utils::compute_merkle(&reversed_txid, tx_index, &reversed_merkle_proof);
+            let median_time_past = commited_header.get_median_time_past();
 
             require!(
-                computed_merkle == commited_header.header.merkle_root,
-                RelayErrorCode::MerkleRoot
+                match operation {
+                    0 => median_time_past < value,
+                    1 => median_time_past <= value,
+                    2 => median_time_past > value,
+                    3 => median_time_past >= value,
+                    4 => median_time_past == value,
+                    _ => false
+                },
+                RelayErrorCode::InvalidMedianTimePast
             );
 
             Ok(())
@@ -420,7 +843,7 @@ pub mod btc_relay {
 
         #[cfg(not(feature = "mocked"))]
         {
-            let main_state = ctx.accounts.main_state.load()?;
+            let main_state = MainStateRef::load(&ctx.accounts.main_state)?;
             let block_height = main_state.block_height;
 
             require!(
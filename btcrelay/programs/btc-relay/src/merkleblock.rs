@@ -0,0 +1,205 @@
+//Partial merkle trees, as used by bitcoin's merkleblock message, proving inclusion of multiple transactions in a block
+//The tree is traversed depth-first, for every node a flag bit specifies whether the node is a parent of a matched
+// transaction, hashes are provided for the nodes that aren't traversed further (and for the matched transactions)
+//https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki#partial-merkle-branch-format
+use anchor_lang::solana_program::hash;
+
+//Number of nodes at the height of the tree (0 being the leaves)
+fn tree_width(total_transactions: u32, height: u32) -> u32 {
+    ((total_transactions as u64 + (1u64 << height) - 1) >> height) as u32
+}
+
+//Height of the merkle tree of the block with total_transactions, equal to the length of the merkle proof of any transaction
+pub fn tree_height(total_transactions: u32) -> u32 {
+    let mut height = 0;
+    while tree_width(total_transactions, height) > 1 {
+        height += 1;
+    }
+    height
+}
+
+//Partial merkle tree traversal state
+struct Traversal<'a> {
+    total_transactions: u32,
+    hashes: &'a [[u8; 32]],
+    flags: &'a [u8],
+    hashes_used: usize,
+    flags_used: usize,
+    matches: Vec<[u8; 32]>
+}
+
+impl<'a> Traversal<'a> {
+
+    fn next_flag(&mut self) -> Option<bool> {
+        let flag = (self.flags.get(self.flags_used / 8)? >> (self.flags_used % 8)) & 0x1 == 1;
+        self.flags_used += 1;
+        Some(flag)
+    }
+
+    fn next_hash(&mut self) -> Option<[u8; 32]> {
+        let hash = *self.hashes.get(self.hashes_used)?;
+        self.hashes_used += 1;
+        Some(hash)
+    }
+
+    //Returns the reversed hash of the node at the height and position, collecting the matched transactions
+    fn traverse(&mut self, height: u32, position: u32) -> Option<[u8; 32]> {
+        let parent_of_match = self.next_flag()?;
+
+        if height==0 || !parent_of_match {
+            let hash = self.next_hash()?;
+            if height==0 && parent_of_match {
+                self.matches.push(hash);
+            }
+            return Some(hash);
+        }
+
+        let left = self.traverse(height-1, position*2)?;
+        let right = if position*2+1 < tree_width(self.total_transactions, height-1) {
+            let right = self.traverse(height-1, position*2+1)?;
+            //Identical children would allow for duplicate transactions (CVE-2012-2459)
+            if right==left {
+                return None;
+            }
+            right
+        } else {
+            left
+        };
+
+        Some(hash::hash(&hash::hashv(&[&left, &right]).to_bytes()).to_bytes())
+    }
+
+}
+
+//Computes the reversed merkle root of the partial merkle tree, returns it along with the reversed transaction IDs
+// of the matched transactions (in the order of their position in the block), or None if the tree is malformed
+pub fn extract_matches(total_transactions: u32, hashes: &[[u8; 32]], flags: &[u8]) -> Option<([u8; 32], Vec<[u8; 32]>)> {
+    if total_transactions==0 || hashes.len() > total_transactions as usize || hashes.len() > flags.len()*8 {
+        return None;
+    }
+
+    let mut traversal = Traversal {
+        total_transactions,
+        hashes,
+        flags,
+        hashes_used: 0,
+        flags_used: 0,
+        matches: Vec::new()
+    };
+
+    let root = traversal.traverse(tree_height(total_transactions), 0)?;
+
+    //All the hashes and all the flags have to be consumed, the padding bits in the last byte have to be zero
+    if traversal.hashes_used != hashes.len() || traversal.flags_used.div_ceil(8) != flags.len() {
+        return None;
+    }
+    if !traversal.flags_used.is_multiple_of(8) && flags[flags.len()-1] >> (traversal.flags_used % 8) != 0 {
+        return None;
+    }
+
+    Some((root, traversal.matches))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex_32(data: &str) -> [u8; 32] {
+        let bytes: Vec<u8> = (0..data.len()).step_by(2).map(|i| u8::from_str_radix(&data[i..i+2], 16).unwrap()).collect();
+        bytes.try_into().unwrap()
+    }
+
+    //Merkleblock of the block 000000000000b731f2eef9e8c63173adfb07e41bd53eb0ef0a6b720d6cb6dea4 with 7 transactions,
+    // matching the transaction at index 4, from the bitcoin developer reference
+    const MERKLE_ROOT: &str = "7f16c5962e8bd963659c793ce370d95f093bc7e367117b3c30c1f8fdd0d97287";
+    const HASHES: [&str; 4] = [
+        "3612262624047ee87660be1a707519a443b1c1ce3d248cbfc6c15870f6c5daa2",
+        "019f5b01d4195ecbc9398fbf3c3b1fa9bb3183301d7a1fb3bd174fcfa40a2b65",
+        "41ed70551dd7e841883ab8f0b16bf04176b7d1480e4f0af9f3d4c3595768d068",
+        "20d2a7bc994987302e5b1ac80fc425fe25f8b63169ea78e68fbaaefa59379bbf"
+    ];
+    const FLAGS: [u8; 1] = [0x1d];
+
+    fn hashes() -> Vec<[u8; 32]> {
+        HASHES.iter().map(|hash| from_hex_32(hash)).collect()
+    }
+
+    #[test]
+    fn valid_tree() {
+        let (root, matches) = extract_matches(7, &hashes(), &FLAGS).unwrap();
+        assert_eq!(root, from_hex_32(MERKLE_ROOT));
+        assert_eq!(matches, vec![from_hex_32(HASHES[1])]);
+    }
+
+    #[test]
+    fn tree_heights() {
+        assert_eq!(tree_height(1), 0);
+        assert_eq!(tree_height(2), 1);
+        assert_eq!(tree_height(7), 3);
+        assert_eq!(tree_height(8), 3);
+        assert_eq!(tree_height(9), 4);
+    }
+
+    #[test]
+    fn trailing_flag_bits() {
+        //Unused flag byte
+        assert!(extract_matches(7, &hashes(), &[0x1d, 0x00]).is_none());
+        //Non-zero padding bit
+        assert!(extract_matches(7, &hashes(), &[0x9d]).is_none());
+        //Not enough flag bits
+        assert!(extract_matches(7, &hashes(), &[]).is_none());
+    }
+
+    #[test]
+    fn leftover_hashes() {
+        let mut hashes = hashes();
+        hashes.push(hashes[0]);
+        assert!(extract_matches(7, &hashes, &FLAGS).is_none());
+
+        //Missing hash
+        assert!(extract_matches(7, &hashes[..3], &FLAGS).is_none());
+    }
+
+    #[test]
+    fn wrong_total_transactions() {
+        //Same tree height with the pruned subtrees covering the difference results in the same merkle root,
+        // the transaction count is therefore only authenticated up to the tree height
+        let (root, _) = extract_matches(8, &hashes(), &FLAGS).unwrap();
+        assert_eq!(root, from_hex_32(MERKLE_ROOT));
+        //Different tree height either fails or results in a different merkle root
+        for total_transactions in [3, 4, 5, 9, 16] {
+            if let Some((root, _)) = extract_matches(total_transactions, &hashes(), &FLAGS) {
+                assert_ne!(root, from_hex_32(MERKLE_ROOT));
+            }
+        }
+        assert!(extract_matches(0, &hashes(), &FLAGS).is_none());
+    }
+
+    #[test]
+    fn duplicate_right_child() {
+        let txid = from_hex_32(HASHES[1]);
+        let other_txid = from_hex_32(HASHES[2]);
+
+        //Root & both leaves are traversed, both leaves matched
+        let (_, matches) = extract_matches(2, &[txid, other_txid], &[0x07]).unwrap();
+        assert_eq!(matches, vec![txid, other_txid]);
+
+        //CVE-2012-2459, identical right child
+        assert!(extract_matches(2, &[txid, txid], &[0x07]).is_none());
+    }
+
+    #[test]
+    fn single_transaction() {
+        let txid = from_hex_32(HASHES[1]);
+
+        let (root, matches) = extract_matches(1, &[txid], &[0x01]).unwrap();
+        assert_eq!(root, txid);
+        assert_eq!(matches, vec![txid]);
+
+        let (root, matches) = extract_matches(1, &[txid], &[0x00]).unwrap();
+        assert_eq!(root, txid);
+        assert!(matches.is_empty());
+
+        assert!(extract_matches(1, &[txid, txid], &[0x01]).is_none());
+    }
+}
@@ -0,0 +1,187 @@
+//Merkle mountain range accumulator of block commitments (sha256 hashes of CommittedBlockHeader data)
+//Leaves are only ever appended, the accumulator is represented by its peaks, with peak at index i
+// being the root of a perfect binary tree of 2^i leaves, present only if bit i of the leaf count is set
+//https://github.com/opentimestamps/opentimestamps-server/blob/master/doc/merkle-mountain-range.md
+use anchor_lang::solana_program::hash;
+
+//Maximum number of peaks, leaf count is a u32
+pub const MAX_PEAKS: usize = 32;
+
+//Hashes 2 child nodes together to get the parent node
+fn hash_nodes(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hash::hashv(&[left, right]).to_bytes()
+}
+
+//Appends a leaf to the accumulator, merging peaks of the same size, in the same way as binary addition
+pub fn append(peaks: &mut [[u8; 32]; MAX_PEAKS], leaf_count: &mut u32, leaf: [u8; 32]) {
+    let mut node = leaf;
+    let mut height = 0;
+    while (*leaf_count >> height) & 0x1 == 1 {
+        node = hash_nodes(&peaks[height], &node);
+        peaks[height] = [0; 32];
+        height += 1;
+    }
+    peaks[height] = node;
+    *leaf_count += 1;
+}
+
+//Verifies that the leaf is included in the accumulator at leaf_index, proof consists of the sibling
+// nodes on the path from the leaf to its peak, ordered from the bottom
+pub fn verify(peaks: &[[u8; 32]; MAX_PEAKS], leaf_count: u32, leaf: [u8; 32], leaf_index: u32, proof: &[[u8; 32]]) -> bool {
+    if leaf_index >= leaf_count {
+        return false;
+    }
+
+    //Find the peak containing the leaf, peaks are ordered from the biggest (left-most) to the smallest
+    let mut peak_start: u32 = 0;
+    let mut peak_height: usize = MAX_PEAKS;
+    for height in (0..MAX_PEAKS).rev() {
+        if (leaf_count >> height) & 0x1 == 0 {
+            continue;
+        }
+        let peak_size: u32 = 1 << height;
+        if leaf_index < peak_start + peak_size {
+            peak_height = height;
+            break;
+        }
+        peak_start += peak_size;
+    }
+
+    if proof.len() != peak_height {
+        return false;
+    }
+
+    let mut node = leaf;
+    let mut index = leaf_index - peak_start;
+    for sibling in proof.iter() {
+        if index & 0x1 == 0 {
+            node = hash_nodes(&node, sibling);
+        } else {
+            node = hash_nodes(sibling, &node);
+        }
+        index >>= 1;
+    }
+
+    node == peaks[peak_height]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(i: u32) -> [u8; 32] {
+        hash::hash(&i.to_le_bytes()).to_bytes()
+    }
+
+    //Root of the perfect binary tree over the leaves, computed naively
+    fn tree_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+        if leaves.len() == 1 {
+            return leaves[0];
+        }
+        let half = leaves.len()/2;
+        hash_nodes(&tree_root(&leaves[..half]), &tree_root(&leaves[half..]))
+    }
+
+    //Sibling path of the leaf at index inside the perfect binary tree over the leaves, ordered from the bottom
+    fn tree_proof(leaves: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+        if leaves.len() == 1 {
+            return Vec::new();
+        }
+        let half = leaves.len()/2;
+        let (mut proof, sibling) = if index < half {
+            (tree_proof(&leaves[..half], index), tree_root(&leaves[half..]))
+        } else {
+            (tree_proof(&leaves[half..], index-half), tree_root(&leaves[..half]))
+        };
+        proof.push(sibling);
+        proof
+    }
+
+    //Splits leaves into the perfect trees of the accumulator, returns (height, start index) of every peak
+    fn peak_ranges(leaf_count: u32) -> Vec<(usize, u32)> {
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for height in (0..MAX_PEAKS).rev() {
+            if (leaf_count >> height) & 0x1 == 1 {
+                ranges.push((height, start));
+                start += 1 << height;
+            }
+        }
+        ranges
+    }
+
+    #[test]
+    fn append_matches_naive_peaks() {
+        let mut peaks = [[0u8; 32]; MAX_PEAKS];
+        let mut leaf_count = 0;
+        let mut leaves = Vec::new();
+        for i in 0..70 {
+            leaves.push(leaf(i));
+            append(&mut peaks, &mut leaf_count, leaf(i));
+            assert_eq!(leaf_count, i+1);
+
+            let mut expected = [[0u8; 32]; MAX_PEAKS];
+            for (height, start) in peak_ranges(leaf_count) {
+                expected[height] = tree_root(&leaves[start as usize..(start as usize + (1 << height))]);
+            }
+            assert_eq!(peaks, expected);
+        }
+    }
+
+    #[test]
+    fn prove_all_leaves() {
+        let mut peaks = [[0u8; 32]; MAX_PEAKS];
+        let mut leaf_count = 0;
+        let mut leaves = Vec::new();
+        for i in 0..70 {
+            leaves.push(leaf(i));
+            append(&mut peaks, &mut leaf_count, leaf(i));
+
+            //Every leaf has to stay provable after peaks get merged
+            for (height, start) in peak_ranges(leaf_count) {
+                let peak_leaves = &leaves[start as usize..(start as usize + (1 << height))];
+                for (offset, peak_leaf) in peak_leaves.iter().enumerate() {
+                    let proof = tree_proof(peak_leaves, offset);
+                    assert_eq!(proof.len(), height);
+                    assert!(verify(&peaks, leaf_count, *peak_leaf, start + offset as u32, &proof));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reject_invalid_proofs() {
+        let mut peaks = [[0u8; 32]; MAX_PEAKS];
+        let mut leaf_count = 0;
+        let mut leaves = Vec::new();
+        for i in 0..13 {
+            leaves.push(leaf(i));
+            append(&mut peaks, &mut leaf_count, leaf(i));
+        }
+
+        //13 leaves = peaks of 8, 4 & 1 leaves, leaf 5 is in the first peak
+        let proof = tree_proof(&leaves[0..8], 5);
+        assert!(verify(&peaks, leaf_count, leaf(5), 5, &proof));
+
+        //Wrong leaf
+        assert!(!verify(&peaks, leaf_count, leaf(6), 5, &proof));
+        //Wrong index, same & different peak
+        assert!(!verify(&peaks, leaf_count, leaf(5), 4, &proof));
+        assert!(!verify(&peaks, leaf_count, leaf(5), 9, &proof));
+        //Index out of range
+        assert!(!verify(&peaks, leaf_count, leaf(5), 13, &proof));
+        //Truncated & extended proof
+        assert!(!verify(&peaks, leaf_count, leaf(5), 5, &proof[..2]));
+        let mut extended = proof.clone();
+        extended.push(peaks[2]);
+        assert!(!verify(&peaks, leaf_count, leaf(5), 5, &extended));
+        //Tampered sibling
+        let mut tampered = proof.clone();
+        tampered[1][0] ^= 0x01;
+        assert!(!verify(&peaks, leaf_count, leaf(5), 5, &tampered));
+
+        //Single leaf peak has an empty proof
+        assert!(verify(&peaks, leaf_count, leaf(12), 12, &[]));
+        assert!(!verify(&peaks, leaf_count, leaf(12), 12, &[leaf(11)]));
+    }
+}
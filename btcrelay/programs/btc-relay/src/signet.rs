@@ -0,0 +1,490 @@
+//Signet block solution verification
+//https://github.com/bitcoin/bips/blob/master/bip-0325.mediawiki
+use anchor_lang::{
+    prelude::*,
+    solana_program::hash,
+    solana_program::secp256k1_recover::secp256k1_recover
+};
+use crate::errors::*;
+use crate::structs::*;
+use crate::btctx;
+use crate::utils;
+use crate::consensus::PARAMS;
+
+//Prefix of the push in the witness commitment output script which contains the signet block solution
+const SIGNET_HEADER: [u8; 4] = [0xec, 0xc7, 0xda, 0xa2];
+
+//Only SIGHASH_ALL signatures are supported
+const SIGHASH_ALL: u8 = 0x01;
+
+//Extracts the signet block solution from the witness commitment output script, returns the output
+// script with the solution removed (leaving only SIGNET_HEADER push) and the solution, or None if the
+// output script doesn't contain a solution
+fn fetch_and_clear_commitment_section(witness_commitment: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    let mut replacement = Vec::with_capacity(witness_commitment.len());
+    let mut solution: Option<&[u8]> = None;
+
+    let mut offset = 0;
+    while offset < witness_commitment.len() {
+        let (opcode, data, size) = btctx::read_script_op(witness_commitment, offset)?;
+        offset += size;
+
+        if data.is_empty() {
+            replacement.push(opcode);
+            continue;
+        }
+
+        //Push only counts if it has the header and some data
+        if solution.is_none() && data.len() > SIGNET_HEADER.len() && data[0..SIGNET_HEADER.len()] == SIGNET_HEADER {
+            solution = Some(&data[SIGNET_HEADER.len()..]);
+            btctx::write_script_push(&SIGNET_HEADER, &mut replacement);
+        } else {
+            btctx::write_script_push(data, &mut replacement);
+        }
+    }
+
+    solution.map(|solution| (replacement, solution))
+}
+
+//Parses the signet block solution into scriptSig and witness stack
+fn parse_solution(solution: &[u8]) -> Option<(&[u8], Vec<&[u8]>)> {
+    let (script_sig, mut offset) = btctx::read_var_slice(solution, 0)?;
+
+    let (witness_count, varint_len) = btctx::read_var_int(solution, offset)?;
+    offset += varint_len;
+
+    let mut witness = Vec::new();
+    for _i in 0..witness_count {
+        let (item, total_len) = btctx::read_var_slice(solution, offset)?;
+        offset += total_len;
+        witness.push(item);
+    }
+
+    //Has to consume all the data
+    if offset!=solution.len() {
+        return None;
+    }
+
+    Some((script_sig, witness))
+}
+
+//Strict DER signature encoding check (BIP66), signature includes the sighash type byte,
+// returns 32-byte r & s values of the signature
+//https://github.com/bitcoin/bips/blob/master/bip-0066.mediawiki
+fn parse_der_signature(sig: &[u8]) -> Option<[u8; 64]> {
+    if sig.len() < 9 || sig.len() > 73 {
+        return None;
+    }
+    if sig[0] != 0x30 || sig[1] as usize != sig.len() - 3 {
+        return None;
+    }
+
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= sig.len() {
+        return None;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 7 != sig.len() {
+        return None;
+    }
+
+    //R & S have to be positive integers without unnecessary zero padding
+    if sig[2] != 0x02 || len_r == 0 || sig[4] & 0x80 != 0 {
+        return None;
+    }
+    if len_r > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0 {
+        return None;
+    }
+    if sig[len_r + 4] != 0x02 || len_s == 0 || sig[len_r + 6] & 0x80 != 0 {
+        return None;
+    }
+    if len_s > 1 && sig[len_r + 6] == 0x00 && sig[len_r + 7] & 0x80 == 0 {
+        return None;
+    }
+
+    let r = strip_leading_zero(&sig[4..(4 + len_r)]);
+    let s = strip_leading_zero(&sig[(6 + len_r)..(6 + len_r + len_s)]);
+    if r.len() > 32 || s.len() > 32 {
+        return None;
+    }
+
+    let mut result = [0u8; 64];
+    result[(32 - r.len())..32].copy_from_slice(r);
+    result[(64 - s.len())..64].copy_from_slice(s);
+    Some(result)
+}
+
+fn strip_leading_zero(num: &[u8]) -> &[u8] {
+    if num.len() > 1 && num[0] == 0x00 {
+        &num[1..]
+    } else {
+        num
+    }
+}
+
+//Checks ECDSA signature (with sighash type byte) against the compressed public key,
+// uses secp256k1_recover syscall and compares the recovered key
+fn check_signature(sig: &[u8], pubkey: &[u8], sighash: &[u8; 32]) -> bool {
+    if sig.is_empty() || sig[sig.len()-1] != SIGHASH_ALL {
+        return false;
+    }
+    let rs = match parse_der_signature(sig) {
+        Some(rs) => rs,
+        None => return false
+    };
+
+    for recovery_id in 0..2 {
+        if let Ok(recovered) = secp256k1_recover(sighash, recovery_id, &rs) {
+            //Recovered key is uncompressed x & y coordinates, compress it
+            let recovered = recovered.to_bytes();
+            let mut compressed = [0u8; 33];
+            compressed[0] = 0x02 | (recovered[63] & 0x01);
+            compressed[1..33].copy_from_slice(&recovered[0..32]);
+            if pubkey == compressed {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+//Parses bare multisig challenge script OP_m <pubkey>... OP_n OP_CHECKMULTISIG,
+// returns the required signature count and public keys
+fn parse_multisig(challenge: &[u8]) -> Option<(usize, Vec<&[u8]>)> {
+    let mut offset = 0;
+
+    let (opcode, _, size) = btctx::read_script_op(challenge, offset)?;
+    if !(btctx::OP_1..=btctx::OP_16).contains(&opcode) {
+        return None;
+    }
+    let required = (opcode - btctx::OP_1 + 1) as usize;
+    offset += size;
+
+    let mut pubkeys = Vec::new();
+    loop {
+        let (opcode, data, size) = btctx::read_script_op(challenge, offset)?;
+        offset += size;
+        if (btctx::OP_1..=btctx::OP_16).contains(&opcode) {
+            if pubkeys.len() != (opcode - btctx::OP_1 + 1) as usize {
+                return None;
+            }
+            break;
+        }
+        if data.len() != 33 {
+            return None;
+        }
+        pubkeys.push(data);
+    }
+
+    if required > pubkeys.len() || challenge.get(offset) != Some(&btctx::OP_CHECKMULTISIG) || offset+1 != challenge.len() {
+        return None;
+    }
+
+    Some((required, pubkeys))
+}
+
+//Verifies scriptSig against the bare multisig challenge script, signatures have to be in the same order as public keys
+fn verify_multisig(challenge: &[u8], script_sig: &[u8], sighash: &[u8; 32]) -> bool {
+    let (required, pubkeys) = match parse_multisig(challenge) {
+        Some(result) => result,
+        None => return false
+    };
+
+    //scriptSig has to be push only, with a dummy OP_0 element (NULLDUMMY) followed by the signatures
+    let mut sigs = Vec::new();
+    let mut offset = 0;
+    while offset < script_sig.len() {
+        match btctx::read_script_op(script_sig, offset) {
+            Some((opcode, data, size)) if opcode <= btctx::OP_PUSHDATA4 => {
+                sigs.push(data);
+                offset += size;
+            },
+            _ => return false
+        }
+    }
+    if sigs.len() != required+1 || !sigs[0].is_empty() {
+        return false;
+    }
+
+    let mut key_index = 0;
+    for sig in sigs[1..].iter() {
+        loop {
+            if key_index >= pubkeys.len() {
+                return false;
+            }
+            key_index += 1;
+            if check_signature(sig, pubkeys[key_index-1], sighash) {
+                break;
+            }
+        }
+    }
+
+    true
+}
+
+//Verifies signet block solution against the challenge script, solution is contained in the block's coinbase transaction
+//https://github.com/bitcoin/bitcoin/blob/master/src/signet.cpp
+pub fn verify_block_solution(header: &BlockHeader, coinbase: &CoinbaseProof, challenge: &[u8]) -> Result<()> {
+    let tx = utils::verify_coinbase(coinbase, &header.merkle_root)?;
+
+    //Solution is contained in the witness commitment output
+    let opt_commitment_index = btctx::get_witness_commitment_index(&tx);
+    require!(
+        opt_commitment_index.is_some(),
+        RelayErrorCode::InvalidSignetSolution
+    );
+    let commitment_output = &tx.outputs[opt_commitment_index.unwrap()];
+
+    let opt_commitment_section = fetch_and_clear_commitment_section(commitment_output.script);
+    require!(
+        opt_commitment_section.is_some(),
+        RelayErrorCode::InvalidSignetSolution
+    );
+    let (replacement_script, solution) = opt_commitment_section.unwrap();
+
+    let opt_solution = parse_solution(solution);
+    require!(
+        opt_solution.is_some(),
+        RelayErrorCode::InvalidSignetSolution
+    );
+    let (script_sig, witness) = opt_solution.unwrap();
+
+    //Only bare (non-segwit) challenges are supported, so witness has to be empty
+    require!(
+        witness.is_empty(),
+        RelayErrorCode::InvalidSignetSolution
+    );
+
+    //Compute the signet merkle root, with the solution removed from the coinbase transaction
+    let data = &coinbase.transaction;
+    let script_start = commitment_output.offset+8;
+    let script_end = script_start + btctx::read_var_slice(data, script_start).unwrap().1;
+    let mut modified_coinbase = Vec::with_capacity(data.len());
+    modified_coinbase.extend_from_slice(&data[..script_start]);
+    btctx::write_var_int(replacement_script.len() as u64, &mut modified_coinbase);
+    modified_coinbase.extend_from_slice(&replacement_script);
+    modified_coinbase.extend_from_slice(&data[script_end..]);
+    let signet_merkle_root = utils::compute_merkle(&btctx::get_reversed_txid(&modified_coinbase), 0, &coinbase.reversed_merkle_proof);
+
+    //Block data committed to in the to_spend transaction
+    let mut block_data = Vec::with_capacity(4+32+32+4);
+    block_data.extend_from_slice(&header.version.to_le_bytes());
+    block_data.extend_from_slice(&header.reversed_prev_blockhash);
+    block_data.extend_from_slice(&signet_merkle_root);
+    block_data.extend_from_slice(&header.timestamp.to_le_bytes());
+
+    //Virtual to_spend transaction, spending an empty UTXO to the challenge script
+    let mut to_spend = Vec::new();
+    to_spend.extend_from_slice(&0u32.to_le_bytes()); //Version
+    to_spend.push(1); //Input count
+    to_spend.extend_from_slice(&[0u8; 32]); //UTXO
+    to_spend.extend_from_slice(&0xFFFFFFFFu32.to_le_bytes()); //Index
+    btctx::write_var_int((2+block_data.len()) as u64, &mut to_spend); //Script len
+    to_spend.push(btctx::OP_0);
+    btctx::write_script_push(&block_data, &mut to_spend);
+    to_spend.extend_from_slice(&0u32.to_le_bytes()); //Sequence
+    to_spend.push(1); //Output count
+    to_spend.extend_from_slice(&0u64.to_le_bytes()); //Value
+    btctx::write_var_int(challenge.len() as u64, &mut to_spend); //Script len
+    to_spend.extend_from_slice(challenge);
+    to_spend.extend_from_slice(&0u32.to_le_bytes()); //Locktime
+
+    //Legacy sighash of the virtual to_sign transaction, spending to_spend output to OP_RETURN
+    let mut to_sign = Vec::new();
+    to_sign.extend_from_slice(&0u32.to_le_bytes()); //Version
+    to_sign.push(1); //Input count
+    to_sign.extend_from_slice(&btctx::get_reversed_txid(&to_spend)); //UTXO
+    to_sign.extend_from_slice(&0u32.to_le_bytes()); //Index
+    btctx::write_var_int(challenge.len() as u64, &mut to_sign); //Script code len
+    to_sign.extend_from_slice(challenge);
+    to_sign.extend_from_slice(&0u32.to_le_bytes()); //Sequence
+    to_sign.push(1); //Output count
+    to_sign.extend_from_slice(&0u64.to_le_bytes()); //Value
+    to_sign.push(1); //Script len
+    to_sign.push(btctx::OP_RETURN);
+    to_sign.extend_from_slice(&0u32.to_le_bytes()); //Locktime
+    to_sign.extend_from_slice(&(SIGHASH_ALL as u32).to_le_bytes()); //Sighash type
+    let sighash = hash::hash(&hash::hash(&to_sign).to_bytes()).to_bytes();
+
+    require!(
+        verify_multisig(challenge, script_sig, &sighash),
+        RelayErrorCode::InvalidSignetSolution
+    );
+
+    Ok(())
+}
+
+//Verifies block solutions of all the signet blockheaders, returns the plain blockheaders
+pub fn verify_block_solutions(data: &[SignetBlockHeader]) -> Result<Vec<BlockHeader>> {
+    require!(
+        PARAMS.signet_challenge.is_some(),
+        RelayErrorCode::UnsupportedNetwork
+    );
+    let challenge = PARAMS.signet_challenge.unwrap();
+
+    let mut headers = Vec::with_capacity(data.len());
+    for signet_header in data.iter() {
+        verify_block_solution(&signet_header.header, &signet_header.coinbase, challenge)?;
+        headers.push(signet_header.header);
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(data: &str) -> Vec<u8> {
+        (0..data.len()).step_by(2).map(|i| u8::from_str_radix(&data[i..i+2], 16).unwrap()).collect()
+    }
+
+    fn from_hex_32(data: &str) -> [u8; 32] {
+        from_hex(data).try_into().unwrap()
+    }
+
+    //Default public signet challenge (1-of-2 bare multisig)
+    const DEFAULT_SIGNET_CHALLENGE: &str = "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be430210359ef5021964fe22d6f8e05b2463c9540ce96883fe3b278760f048f5189f2e6c452ae";
+
+    //Block signed by a 1-of-1 bare multisig challenge, the vector was generated by an independent BIP325
+    // implementation, block has 2 transactions, with the coinbase's sibling being an arbitrary hash
+    const CHALLENGE: &str = "5121020121ef709ce7b0ceea109d4994518aa4a28592591ff5380185356c1d57c03cad51ae";
+    const COINBASE: &str = "02000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0503a0860100ffffffff0200f2052a01000000160014000102030405060708090a0b0c0d0e0f101112130000000000000000786a24aa21a9edba1c566a4bad288c22a0b7511458c92ca5822cd41632e51806e9ea75ed12d13d4c50ecc7daa24a00483045022100d00100bd578349731282ac61f6a8fb204ed2c5f8b154ea0103c167940bb5bfe8022038e7ab7fea51b593eae977016b49e8986547d012815da81b878169bd0c0fdf05010000000000";
+    const SIBLING: &str = "7d10de8554ed5ca40f9d0f0e0f4375b5b338af3fb96d33c9b2f53b5289b8f4fe";
+    const PREV_BLOCKHASH: &str = "84fd9bac333ad79154348296204fa7f8c537a96e08983e5f73b3f5aca8e8edf7";
+    const MERKLE_ROOT: &str = "60f1f315812918b19123558a125574e9885b8f279be213a08a936fa95c35f483";
+
+    fn signed_block() -> (BlockHeader, CoinbaseProof) {
+        let header = BlockHeader {
+            version: 0x20000000,
+            reversed_prev_blockhash: from_hex_32(PREV_BLOCKHASH),
+            merkle_root: from_hex_32(MERKLE_ROOT),
+            timestamp: 1700000000,
+            nbits: 0x1e0377ae,
+            nonce: 12345
+        };
+        let coinbase = CoinbaseProof {
+            transaction: from_hex(COINBASE),
+            reversed_merkle_proof: vec![from_hex_32(SIBLING)]
+        };
+        (header, coinbase)
+    }
+
+    #[test]
+    fn parse_default_signet_challenge() {
+        let challenge = from_hex(DEFAULT_SIGNET_CHALLENGE);
+        let (required, pubkeys) = parse_multisig(&challenge).unwrap();
+        assert_eq!(required, 1);
+        assert_eq!(pubkeys.len(), 2);
+        assert_eq!(pubkeys[0], &challenge[2..35]);
+        assert_eq!(pubkeys[1], &challenge[36..69]);
+    }
+
+    #[test]
+    fn parse_invalid_challenge() {
+        let challenge = from_hex(DEFAULT_SIGNET_CHALLENGE);
+
+        //Key count mismatch
+        let mut invalid = challenge.clone();
+        invalid[challenge.len()-2] = btctx::OP_1 + 2;
+        assert!(parse_multisig(&invalid).is_none());
+
+        //More required signatures than keys
+        let mut invalid = challenge.clone();
+        invalid[0] = btctx::OP_1 + 2;
+        assert!(parse_multisig(&invalid).is_none());
+
+        //Trailing data
+        let mut invalid = challenge.clone();
+        invalid.push(btctx::OP_CHECKMULTISIG);
+        assert!(parse_multisig(&invalid).is_none());
+
+        //Not a multisig
+        assert!(parse_multisig(&challenge[..challenge.len()-1]).is_none());
+    }
+
+    #[test]
+    fn parse_der_signatures() {
+        let coinbase = from_hex(COINBASE);
+        let sig_start = coinbase.len() - 4 - 1 - 72;
+        let sig = &coinbase[sig_start..sig_start+72];
+
+        let rs = parse_der_signature(sig).unwrap();
+        assert_eq!(rs[0..32], sig[5..37]);
+        assert_eq!(rs[32..64], sig[39..71]);
+
+        //Invalid total length
+        let mut invalid = sig.to_vec();
+        invalid[1] += 1;
+        assert!(parse_der_signature(&invalid).is_none());
+
+        //Unnecessary zero padding of R
+        let mut invalid = sig.to_vec();
+        invalid[5] = 0x00;
+        invalid[6] = 0x00;
+        assert!(parse_der_signature(&invalid).is_none());
+
+        //Negative S
+        let mut invalid = sig.to_vec();
+        invalid[39] = 0x80;
+        assert!(parse_der_signature(&invalid).is_none());
+
+        //Too short
+        assert!(parse_der_signature(&sig[..8]).is_none());
+    }
+
+    #[test]
+    fn valid_block_solution() {
+        let (header, coinbase) = signed_block();
+        verify_block_solution(&header, &coinbase, &from_hex(CHALLENGE)).unwrap();
+    }
+
+    #[test]
+    fn block_solution_commits_to_header() {
+        let challenge = from_hex(CHALLENGE);
+
+        let (mut header, coinbase) = signed_block();
+        header.timestamp += 1;
+        assert!(verify_block_solution(&header, &coinbase, &challenge).is_err());
+
+        let (mut header, coinbase) = signed_block();
+        header.reversed_prev_blockhash[0] ^= 0x01;
+        assert!(verify_block_solution(&header, &coinbase, &challenge).is_err());
+
+        //Nonce & nbits are not committed to by the solution
+        let (mut header, coinbase) = signed_block();
+        header.nonce += 1;
+        header.nbits = 0x1d00ffff;
+        verify_block_solution(&header, &coinbase, &challenge).unwrap();
+    }
+
+    #[test]
+    fn block_solution_wrong_challenge() {
+        let (header, coinbase) = signed_block();
+        assert!(verify_block_solution(&header, &coinbase, &from_hex(DEFAULT_SIGNET_CHALLENGE)).is_err());
+    }
+
+    //Updates the header's merkle root after the coinbase transaction was modified
+    fn update_merkle_root(header: &mut BlockHeader, coinbase: &CoinbaseProof) {
+        header.merkle_root = utils::compute_merkle(&btctx::get_reversed_txid(&coinbase.transaction), 0, &coinbase.reversed_merkle_proof);
+    }
+
+    #[test]
+    fn block_solution_tampered_signature() {
+        let (mut header, mut coinbase) = signed_block();
+        let sig_end = coinbase.transaction.len() - 4 - 1 - 1;
+        coinbase.transaction[sig_end-1] ^= 0x01;
+        update_merkle_root(&mut header, &coinbase);
+        assert!(verify_block_solution(&header, &coinbase, &from_hex(CHALLENGE)).is_err());
+    }
+
+    #[test]
+    fn missing_block_solution() {
+        let (mut header, mut coinbase) = signed_block();
+        let commitment_script_start = 4+1+32+4+1+5+4+1+8+1+22+8+1;
+        //Corrupt the signet header of the solution push
+        coinbase.transaction[commitment_script_start+38+2] ^= 0x01;
+        update_merkle_root(&mut header, &coinbase);
+        assert!(verify_block_solution(&header, &coinbase, &from_hex(CHALLENGE)).is_err());
+    }
+}
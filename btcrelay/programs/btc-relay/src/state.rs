@@ -1,9 +1,31 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{
+    prelude::*,
+    solana_program::system_instruction::MAX_PERMITTED_DATA_LENGTH,
+    Discriminator,
+    ZeroCopy
+};
+use std::cell::{Ref, RefMut};
+use std::ops::{Deref, DerefMut};
+use crate::errors::*;
+use crate::mmr;
+use crate::u256::U256;
 
-//How many block commitments should be kept in main state PDA's ring buffer
-const PRUNING_FACTOR_U32: u32 = 250;
-const PRUNING_FACTOR: usize = PRUNING_FACTOR_U32 as usize;
+//Bounds of the pruning factor - how many block commitments are kept in main state PDA's ring buffer, it is
+// set in the initialize instruction and can be increased later with the resize_main_state instruction
+//Programs relying on the ring buffer (e.g. swap program's max confirmations) can count on at least
+// MIN_PRUNING_FACTOR blocks being kept, the maximum is given by the maximum account size
+pub const MIN_PRUNING_FACTOR: u32 = 250;
+pub const MAX_PRUNING_FACTOR: u32 = ((MAX_PERMITTED_DATA_LENGTH as usize - MainState::space(0))/32) as u32;
 
+//Main state PDA created by previous versions of the program had a fixed 250 block commitments
+// ring buffer right after the MainState fields up to chain_work (stored as 32-byte big endian array), and no MMR accumulator
+pub const LEGACY_PRUNING_FACTOR: u32 = 250;
+pub const LEGACY_HEADER_SIZE: usize = 4+4+4+4+8+32+32+32;
+pub const LEGACY_SPACE: usize = 8+8+4+4+4+32+8+4+32+32+(LEGACY_PRUNING_FACTOR as usize*32);
+
+//Fixed size part of the main state PDA, the ring buffer storing block data commitments
+// (sha256 hashes of CommittedBlockHeader data) of length pruning_factor is stored in the account data
+// right after it, use MainStateRef & MainStateRefMut to access the ring buffer
 #[account(zero_copy)]
 #[repr(C)]
 pub struct MainState {
@@ -11,67 +33,115 @@ pub struct MainState {
     pub last_diff_adjustment: u32, //Timestamp of the last difficulty adjustment block
     pub block_height: u32, //Current blockheight
     pub total_blocks: u32, //Total number of blocks validated
-    
+
     pub fork_counter: u64, //Used for indexing fork PDA's
 
     pub tip_commit_hash: [u8; 32], //Blockheader data commitment hash for the latest block - blockchain tip
     pub tip_block_hash: [u8; 32], //Blockhash of the latest block - blockchain tip
 
-    pub chain_work: [u8; 32], //Accumulated work of the chain
-    pub block_commitments: [[u8; 32]; 250], //Ring buffer storing block data commitments (sha256 hashes of CommittedBlockHeader data)
+    pub chain_work: U256, //Accumulated work of the chain
+
+    pub mmr_start_height: u32, //Blockheight of the first leaf in the MMR accumulator - the initial block
+    pub mmr_leaf_count: u32, //Number of leaves (block commitments) in the MMR accumulator
+    pub mmr_peaks: [[u8; 32]; 32], //Peaks of the MMR accumulator, storing block commitments of all blocks that were pruned from the ring buffer
+
+    pub pruning_factor: u32, //Length of the ring buffer
+
+    pub pending_fork_since: u32, //Timestamp since when the pending deep reorg fork is pending
+    pub pending_fork_id: u64, //Fork id of the pending deep reorg fork
+    pub pending_fork_chain_work: U256 //Chain work of the pending deep reorg fork, zero if no deep reorg is pending
 }
 
 impl MainState {
-    
-    pub fn space() -> usize {
-        8+8+4+4+4+32+8+4+32+32+(PRUNING_FACTOR*32)
+
+    //Whether a deep reorg fork with more chain work than the main chain is pending
+    pub fn is_deep_reorg_pending(&self) -> bool {
+        !self.pending_fork_chain_work.is_zero()
+    }
+
+    //Clears the pending deep reorg fork once the main chain's work is no longer lower than its work
+    pub fn update_pending_fork(&mut self) {
+        if self.chain_work >= self.pending_fork_chain_work {
+            self.reset_pending_fork();
+        }
+    }
+
+    //Clears the pending deep reorg fork if it is the fork with fork_id, called when the fork gets applied or closed
+    pub fn clear_pending_fork(&mut self, fork_id: u64) {
+        if self.is_deep_reorg_pending() && self.pending_fork_id == fork_id {
+            self.reset_pending_fork();
+        }
+    }
+
+    fn reset_pending_fork(&mut self) {
+        self.pending_fork_since = 0;
+        self.pending_fork_id = 0;
+        self.pending_fork_chain_work = U256::ZERO;
+    }
+
+    //Whether the fork forking after the start_height block can no longer be extended or applied, because the start_height
+    // block was already pruned from the ring buffer, or main chain's block commitments the fork would replace were already
+    // appended to the MMR accumulator
+    pub fn is_fork_start_pruned(&self, start_height: u32) -> bool {
+        start_height+self.pruning_factor <= self.block_height ||
+            start_height+1 < self.mmr_start_height+self.mmr_leaf_count
+    }
+
+    //Size of the main state PDA with the ring buffer of pruning_factor block commitments
+    pub const fn space(pruning_factor: u32) -> usize {
+        8+std::mem::size_of::<MainState>()+(pruning_factor as usize*32)
     }
 
     //Get's the position on the ring buffer corresponding to the block_height,
-    // returns 0 or PRUNING_FACTOR in edge cases
+    // returns 0 or pruning_factor in edge cases
     pub fn get_position(&self, block_height: u32) -> usize {
         if self.start_height<=block_height {
             let pos = block_height-self.start_height;
-            if pos>=PRUNING_FACTOR_U32 {
+            if pos>=self.pruning_factor {
                 return 0;
             }
             pos as usize
         } else {
             let pos = self.start_height-block_height;
-            if pos>=PRUNING_FACTOR_U32 {
-                return PRUNING_FACTOR;
+            if pos>=self.pruning_factor {
+                return self.pruning_factor as usize;
             }
-            (PRUNING_FACTOR_U32-pos) as usize
+            (self.pruning_factor-pos) as usize
         }
     }
 
-    //Get's the commitment for a block_height,
+    //Get's the commitment for a block_height from the ring buffer,
     // returning empty array [0; 32] in edge cases
-    pub fn get_commitment(&self, block_height: u32) -> [u8; 32] {
+    pub fn get_commitment(&self, block_commitments: &[[u8; 32]], block_height: u32) -> [u8; 32] {
         //Check block_height more than than tip
         if block_height>self.block_height {
             return [0; 32];
         }
         //Check block_height out of bounds for the ring buffer
-        if block_height<=self.block_height-PRUNING_FACTOR_U32 {
+        if block_height+self.pruning_factor<=self.block_height {
             return [0; 32];
         }
         let pos = self.get_position(block_height);
-        if pos==PRUNING_FACTOR {
+        if pos==self.pruning_factor as usize {
             return [0; 32];
         }
-        self.block_commitments[pos]
+        block_commitments[pos]
     }
 
     //Stores the block commitment for the specified block_height in a ring buffer
     // returns false in case that block commitment would fall out of bounds for
-    // ring buffer (more than PRUNING_FACTOR blocks in the past)
-    pub fn store_block_commitment(&mut self, block_height: u32, block_commitment: [u8; 32]) -> bool {
+    // ring buffer (more than pruning_factor blocks in the past)
+    //Block commitment being pruned from the ring buffer is appended to the MMR accumulator, as it can no longer be re-orged
+    // (forks starting before it are rejected by is_fork_start_pruned on every extension and when being applied)
+    pub fn store_block_commitment(&mut self, block_commitments: &mut [[u8; 32]], block_height: u32, block_commitment: [u8; 32]) -> bool {
         let position = self.get_position(block_height);
-        if position==PRUNING_FACTOR {
+        if position==self.pruning_factor as usize {
             return false;
         }
-        self.block_commitments[position] =  block_commitment;
+        if block_height.checked_sub(self.pruning_factor) == Some(self.mmr_start_height + self.mmr_leaf_count) {
+            mmr::append(&mut self.mmr_peaks, &mut self.mmr_leaf_count, block_commitments[position]);
+        }
+        block_commitments[position] =  block_commitment;
         if position==0 {
             self.start_height = block_height;
         }
@@ -79,32 +149,510 @@ impl MainState {
         true
     }
 
+    //Overwrites the ring buffer with fork's block commitments, forking after the start_height block, block commitments
+    // not fitting into the ring buffer anymore (more than pruning_factor blocks before the fork's tip) are appended
+    // straight to the MMR accumulator, along with main chain's block commitments pruned by the fork, fails if main chain's
+    // block commitments replaced by the fork were already appended to the MMR accumulator
+    pub fn apply_fork(&mut self, block_commitments: &mut [[u8; 32]], start_height: u32, fork_commitments: &[[u8; 32]]) -> Result<()> {
+        require!(
+            start_height+1 >= self.mmr_start_height+self.mmr_leaf_count,
+            RelayErrorCode::ForkTooOld
+        );
+
+        let tip_height = start_height+fork_commitments.len() as u32;
+        let window_start = (tip_height+1).saturating_sub(self.pruning_factor);
+
+        //Main chain's block commitments before the fork being pruned from the ring buffer
+        while self.mmr_start_height+self.mmr_leaf_count <= start_height && self.mmr_start_height+self.mmr_leaf_count < window_start {
+            let block_commitment = self.get_commitment(block_commitments, self.mmr_start_height+self.mmr_leaf_count);
+            mmr::append(&mut self.mmr_peaks, &mut self.mmr_leaf_count, block_commitment);
+        }
+
+        for (i, block_commitment) in fork_commitments.iter().enumerate() {
+            let block_height = start_height+1+i as u32;
+            if block_height<window_start {
+                //MMR accumulator already ends right before this block here
+                mmr::append(&mut self.mmr_peaks, &mut self.mmr_leaf_count, *block_commitment);
+                continue;
+            }
+            let position = self.get_position(block_height);
+            if position==self.pruning_factor as usize {
+                continue;
+            }
+            block_commitments[position] = *block_commitment;
+            if position==0 {
+                self.start_height = block_height;
+            }
+            self.total_blocks += 1;
+        }
+
+        Ok(())
+    }
+
+    //Verifies that the block commitment for a block_height pruned from the ring buffer
+    // is included in the MMR accumulator
+    pub fn verify_historic_commitment(&self, block_height: u32, block_commitment: [u8; 32], mmr_proof: &[[u8; 32]]) -> bool {
+        if block_height<self.mmr_start_height {
+            return false;
+        }
+        mmr::verify(&self.mmr_peaks, self.mmr_leaf_count, block_commitment, block_height-self.mmr_start_height, mmr_proof)
+    }
+
+}
+
+//Checks the main state PDA data, returns the length of the ring buffer
+fn check_main_state_data(data: &[u8]) -> Result<usize> {
+    require!(
+        data.len() != LEGACY_SPACE,
+        RelayErrorCode::MainStateNotMigrated
+    );
+    require!(
+        data.len() >= MainState::space(0) && data[0..8] == MainState::discriminator(),
+        ErrorCode::AccountDiscriminatorMismatch
+    );
+    let pruning_factor = bytemuck::from_bytes::<MainState>(&data[8..MainState::space(0)]).pruning_factor;
+    require!(
+        data.len() >= MainState::space(pruning_factor),
+        ErrorCode::AccountDidNotDeserialize
+    );
+    Ok(pruning_factor as usize*32)
+}
+
+//Main state PDA along with its ring buffer of block commitments
+pub struct MainStateRef<'a> {
+    state: Ref<'a, MainState>,
+    block_commitments: Ref<'a, [[u8; 32]]>
+}
+
+impl<'a> MainStateRef<'a> {
+
+    pub fn load<'info>(main_state: &'a AccountLoader<'info, MainState>) -> Result<Self> {
+        let data = main_state.as_ref().try_borrow_data()?;
+        let ring_buffer_size = check_main_state_data(&data)?;
+        let (state, block_commitments) = Ref::map_split(data, |data| data[8..MainState::space(0)+ring_buffer_size].split_at(MainState::space(0)-8));
+        Ok(MainStateRef {
+            state: Ref::map(state, bytemuck::from_bytes),
+            block_commitments: Ref::map(block_commitments, bytemuck::cast_slice)
+        })
+    }
+
+    pub fn get_commitment(&self, block_height: u32) -> [u8; 32] {
+        self.state.get_commitment(&self.block_commitments, block_height)
+    }
+
+}
+
+impl<'a> Deref for MainStateRef<'a> {
+    type Target = MainState;
+
+    fn deref(&self) -> &MainState {
+        &self.state
+    }
+}
+
+//Mutable main state PDA along with its ring buffer of block commitments
+pub struct MainStateRefMut<'a> {
+    state: RefMut<'a, MainState>,
+    block_commitments: RefMut<'a, [[u8; 32]]>
 }
 
+impl<'a> MainStateRefMut<'a> {
+
+    pub fn load_mut<'info>(main_state: &'a AccountLoader<'info, MainState>) -> Result<Self> {
+        let data = main_state.as_ref().try_borrow_mut_data()?;
+        let ring_buffer_size = check_main_state_data(&data)?;
+        Ok(Self::split(data, ring_buffer_size))
+    }
+
+    //Loads the main state PDA for initialization, the ring buffer spans the rest of the account data
+    pub fn load_init<'info>(main_state: &'a AccountLoader<'info, MainState>) -> Result<Self> {
+        let data = main_state.as_ref().try_borrow_mut_data()?;
+        require!(
+            data.len() >= MainState::space(0) && data[0..8] == [0; 8],
+            ErrorCode::AccountDiscriminatorAlreadySet
+        );
+        let ring_buffer_size = (data.len()-MainState::space(0)) / 32 * 32;
+        let mut result = Self::split(data, ring_buffer_size);
+        result.state.pruning_factor = (ring_buffer_size/32) as u32;
+        Ok(result)
+    }
+
+    fn split(data: RefMut<'a, &mut [u8]>, ring_buffer_size: usize) -> Self {
+        let (state, block_commitments) = RefMut::map_split(data, |data| data[8..MainState::space(0)+ring_buffer_size].split_at_mut(MainState::space(0)-8));
+        MainStateRefMut {
+            state: RefMut::map(state, bytemuck::from_bytes_mut),
+            block_commitments: RefMut::map(block_commitments, bytemuck::cast_slice_mut)
+        }
+    }
+
+    pub fn get_commitment(&self, block_height: u32) -> [u8; 32] {
+        self.state.get_commitment(&self.block_commitments, block_height)
+    }
+
+    pub fn store_block_commitment(&mut self, block_height: u32, block_commitment: [u8; 32]) -> bool {
+        self.state.store_block_commitment(&mut self.block_commitments, block_height, block_commitment)
+    }
+
+    pub fn apply_fork(&mut self, start_height: u32, fork_commitments: &[[u8; 32]]) -> Result<()> {
+        self.state.apply_fork(&mut self.block_commitments, start_height, fork_commitments)
+    }
+
+}
+
+impl<'a> Deref for MainStateRefMut<'a> {
+    type Target = MainState;
+
+    fn deref(&self) -> &MainState {
+        &self.state
+    }
+}
+
+impl<'a> DerefMut for MainStateRefMut<'a> {
+    fn deref_mut(&mut self) -> &mut MainState {
+        &mut self.state
+    }
+}
+
+//Fixed size part of the fork PDA, the buffer storing block data commitments (sha256 hashes of CommittedBlockHeader data)
+// of the fork's blocks is stored in the account data right after it, the account is grown as the fork is extended,
+// use ForkStateRefMut to access the buffer
 #[account(zero_copy)]
 #[repr(C)]
 pub struct ForkState {
     pub initialized: u32, //1 - initialized, 0 - not yet initialized (boolean messes up the padding for zero_copy, so u32 is used instead)
     pub start_height: u32, //Blockheight of last block that is also in main chain
     pub length: u32, //Current length of the fork
+    pub last_submission: u32, //Timestamp of the last blockheaders submission to the fork
 
     pub tip_commit_hash: [u8; 32], //Blockheader data commitment hash for the latest block - fork tip
     pub tip_block_hash: [u8; 32], //Blockhash of the latest block - fork tip
-
-    pub block_commitments: [[u8; 32]; 250], //Buffer storing block data commitments (sha256 hashes of CommittedBlockHeader data)
+    pub tip_chain_work: U256 //Accumulated work of the fork's chain at the fork tip
 }
 
+//Forks with no more chain work than the main chain, which weren't extended for STALE_FORK_TIMEOUT seconds,
+// are considered abandoned, such forks can no longer be extended and can be closed by anyone
+pub const STALE_FORK_TIMEOUT: u32 = 24*60*60;
+
 impl ForkState {
-    
-    pub fn space() -> usize {
-        8+4+4+4+32+32+(PRUNING_FACTOR*32)
-    }
-    
-    //Stores block commitment in the array, no ring buffer is implemented here,
-    // limiting the maximum fork length to PRUNING_FACTOR blocks
-    pub fn store_block_commitment(&mut self, block_commitment: [u8; 32]) {
-        self.block_commitments[self.length as usize] = block_commitment;
-        self.length += 1;
+
+    //Whether the fork can no longer become the main chain, either because its start was already pruned from the main chain,
+    // or because it is abandoned while having no more chain work than the main chain
+    pub fn is_stale(&self, main_state: &MainState, timestamp: u32) -> bool {
+        main_state.is_fork_start_pruned(self.start_height) || (
+            self.tip_chain_work <= main_state.chain_work &&
+            timestamp >= self.last_submission.saturating_add(STALE_FORK_TIMEOUT)
+        )
+    }
+
+}
+
+//Maximum number of distinct submitters that can contribute to a shared fork PDA
+pub const MAX_FORK_CONTRIBUTORS: usize = 16;
+//Minimum lamports a submitter has to pay for the shared fork PDA's rent to become its contributor (covered by the rent of 5 block commitments),
+// such that the contributor slots can't be filled up by cheap submissions
+pub const MIN_FORK_CONTRIBUTION: u64 = 1_000_000;
+
+//Submitter that contributed to a shared fork PDA, along with the lamports it paid for the PDA's rent
+#[zero_copy]
+#[repr(C)]
+pub struct ForkContributor {
+    pub submitter: Pubkey,
+    pub lamports: u64
+}
+
+//Fixed size part of the shared fork PDA, which any submitter can append to, the buffer storing block data commitments
+// is stored in the account data right after it, same as for the ForkState, use ForkStateRefMut to access the buffer
+#[account(zero_copy)]
+#[repr(C)]
+pub struct SharedForkState {
+    pub fork: ForkState,
+
+    pub contributor_count: u64, //Number of contributors in the contributors array (u64 keeps the contributors array aligned for zero_copy)
+    pub contributors: [ForkContributor; MAX_FORK_CONTRIBUTORS] //Contributors in the order of their first contribution, the first one being the creator
+}
+
+impl SharedForkState {
+
+    pub fn get_contributors(&self) -> &[ForkContributor] {
+        &self.contributors[..self.contributor_count as usize]
+    }
+
+    //Adds lamports paid for the PDA's rent by the submitter, fails if the submitter is not yet a contributor and either
+    // its contribution is lower than MIN_FORK_CONTRIBUTION, or there is no more space for new contributors
+    pub fn add_contribution(&mut self, submitter: &Pubkey, lamports: u64) -> Result<()> {
+        let count = self.contributor_count as usize;
+        if let Some(contributor) = self.contributors[..count].iter_mut().find(|contributor| contributor.submitter == *submitter) {
+            contributor.lamports += lamports;
+            return Ok(());
+        }
+
+        require!(
+            lamports >= MIN_FORK_CONTRIBUTION,
+            RelayErrorCode::ForkContributionTooLow
+        );
+        require!(
+            count < MAX_FORK_CONTRIBUTORS,
+            RelayErrorCode::ForkContributorsFull
+        );
+
+        self.contributors[count] = ForkContributor {
+            submitter: *submitter,
+            lamports
+        };
+        self.contributor_count += 1;
+
+        Ok(())
+    }
+
+}
+
+//Fork PDA with the buffer of block commitments stored in the account data right after it
+pub trait ForkAccount: ZeroCopy + Owner {
+
+    fn fork(&self) -> &ForkState;
+    fn fork_mut(&mut self) -> &mut ForkState;
+
+    //Size of the fork PDA able to store capacity block commitments
+    fn space(capacity: u32) -> usize {
+        8+std::mem::size_of::<Self>()+(capacity as usize*32)
+    }
+
+}
+
+impl ForkAccount for ForkState {
+
+    fn fork(&self) -> &ForkState {
+        self
+    }
+
+    fn fork_mut(&mut self) -> &mut ForkState {
+        self
+    }
+
+}
+
+impl ForkAccount for SharedForkState {
+
+    fn fork(&self) -> &ForkState {
+        &self.fork
+    }
+
+    fn fork_mut(&mut self) -> &mut ForkState {
+        &mut self.fork
+    }
+
+}
+
+//Mutable fork PDA along with its buffer of block commitments, dereferences to the ForkState
+pub struct ForkStateRefMut<'a, T: ForkAccount = ForkState> {
+    state: RefMut<'a, T>,
+    block_commitments: RefMut<'a, [[u8; 32]]>
+}
+
+impl<'a, T: ForkAccount> ForkStateRefMut<'a, T> {
+
+    //Loads the fork PDA, the buffer spans the rest of the account data, init specifies if the PDA is being initialized
+    pub fn load<'info>(fork_state: &'a AccountLoader<'info, T>, init: bool) -> Result<Self> {
+        let data = fork_state.as_ref().try_borrow_mut_data()?;
+        require!(
+            data.len() >= T::space(0),
+            ErrorCode::AccountDidNotDeserialize
+        );
+        if init {
+            require!(
+                data[0..8] == [0; 8],
+                ErrorCode::AccountDiscriminatorAlreadySet
+            );
+        } else {
+            require!(
+                data[0..8] == T::discriminator(),
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+        }
+        let buffer_end = T::space(0)+(data.len()-T::space(0))/32*32;
+        let (state, block_commitments) = RefMut::map_split(data, |data| data[8..buffer_end].split_at_mut(T::space(0)-8));
+        Ok(ForkStateRefMut {
+            state: RefMut::map(state, bytemuck::from_bytes_mut),
+            block_commitments: RefMut::map(block_commitments, bytemuck::cast_slice_mut)
+        })
+    }
+
+    //Stores block commitment in the buffer, fails if the buffer is already full
+    pub fn store_block_commitment(&mut self, block_commitment: [u8; 32]) -> Result<()> {
+        let position = self.state.fork().length as usize;
+        require!(
+            position < self.block_commitments.len(),
+            RelayErrorCode::ForkBufferFull
+        );
+        self.block_commitments[position] = block_commitment;
+        self.state.fork_mut().length += 1;
+        Ok(())
+    }
+
+    pub fn get_block_commitments(&self) -> &[[u8; 32]] {
+        &self.block_commitments[..self.state.fork().length as usize]
+    }
+
+    //Whole fork PDA, including the fields specific to the fork PDA type
+    pub fn account(&self) -> &T {
+        &self.state
+    }
+
+    pub fn account_mut(&mut self) -> &mut T {
+        &mut self.state
+    }
+
+}
+
+impl<'a, T: ForkAccount> Deref for ForkStateRefMut<'a, T> {
+    type Target = ForkState;
+
+    fn deref(&self) -> &ForkState {
+        self.state.fork()
+    }
+}
+
+impl<'a, T: ForkAccount> DerefMut for ForkStateRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut ForkState {
+        self.state.fork_mut()
+    }
+}
+
+//Fee pool PDA funded by the relay's consumers, paying out header_reward lamports to the submitters for every block header
+// accepted into the main chain
+#[account(zero_copy)]
+#[repr(C)]
+pub struct FeePool {
+    pub authority: Pubkey, //Account allowed to change the header reward & withdraw from the fee pool
+    pub header_reward: u64, //Lamports paid out for every block header accepted into the main chain
+
+    pub total_headers: u64, //Total number of rewarded block headers
+    pub total_rewards: u64 //Total lamports paid out to the submitters
+}
+
+//Per submitter PDA, keeping the accounting of the rewards paid out to the submitter from the fee pool
+#[account(zero_copy)]
+#[repr(C)]
+pub struct SubmitterRewards {
+    pub submitter: Pubkey,
+
+    pub total_headers: u64, //Total number of rewarded block headers submitted by the submitter
+    pub total_rewards: u64 //Total lamports paid out to the submitter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRUNING_FACTOR: u32 = 10;
+    const START_HEIGHT: u32 = 100;
+
+    fn commitment(chain: u8, block_height: u32) -> [u8; 32] {
+        let mut commitment = [chain; 32];
+        commitment[..4].copy_from_slice(&block_height.to_le_bytes());
+        commitment
+    }
+
+    //Main state initialized at START_HEIGHT, with main chain's block commitments stored up to tip_height
+    fn main_state(tip_height: u32) -> (MainState, Vec<[u8; 32]>) {
+        let mut state: MainState = bytemuck::Zeroable::zeroed();
+        let mut block_commitments = vec![[0; 32]; PRUNING_FACTOR as usize];
+        state.pruning_factor = PRUNING_FACTOR;
+        state.start_height = START_HEIGHT;
+        state.mmr_start_height = START_HEIGHT;
+        for block_height in START_HEIGHT..=tip_height {
+            assert!(state.store_block_commitment(&mut block_commitments, block_height, commitment(0, block_height)));
+            state.block_height = block_height;
+        }
+        (state, block_commitments)
+    }
+
+    #[test]
+    fn apply_fork() {
+        let (mut state, mut block_commitments) = main_state(112);
+        let fork_commitments: Vec<[u8; 32]> = (104..=118).map(|block_height| commitment(1, block_height)).collect();
+
+        assert!(!state.is_fork_start_pruned(103));
+        state.apply_fork(&mut block_commitments, 103, &fork_commitments).unwrap();
+        state.block_height = 118;
+
+        //Main chain's block commitments up to the fork's start, followed by the fork's ones not fitting the ring buffer
+        let mut peaks = [[0; 32]; 32];
+        let mut leaf_count = 0;
+        for block_height in 100..=103 {
+            mmr::append(&mut peaks, &mut leaf_count, commitment(0, block_height));
+        }
+        for block_height in 104..=108 {
+            mmr::append(&mut peaks, &mut leaf_count, commitment(1, block_height));
+        }
+        assert_eq!(state.mmr_leaf_count, leaf_count);
+        assert_eq!(state.mmr_peaks, peaks);
+
+        for block_height in 109..=118 {
+            assert_eq!(state.get_commitment(&block_commitments, block_height), commitment(1, block_height));
+        }
+    }
+
+    #[test]
+    fn apply_fork_pruned_start() {
+        let (mut state, mut block_commitments) = main_state(104);
+        let fork_commitments: Vec<[u8; 32]> = (103..=117).map(|block_height| commitment(1, block_height)).collect();
+        assert!(!state.is_fork_start_pruned(102));
+
+        //Main chain advances past the pruning factor, its blocks replaced by the fork are appended to the MMR accumulator
+        for block_height in 105..=115 {
+            assert!(state.store_block_commitment(&mut block_commitments, block_height, commitment(0, block_height)));
+            state.block_height = block_height;
+        }
+        assert_eq!(state.mmr_start_height+state.mmr_leaf_count, 106);
+        assert!(state.is_fork_start_pruned(102));
+
+        let leaf_count = state.mmr_leaf_count;
+        let peaks = state.mmr_peaks;
+        assert!(state.apply_fork(&mut block_commitments, 102, &fork_commitments).is_err());
+        assert_eq!(state.mmr_leaf_count, leaf_count);
+        assert_eq!(state.mmr_peaks, peaks);
+    }
+
+    #[test]
+    fn add_contribution() {
+        let mut state: SharedForkState = bytemuck::Zeroable::zeroed();
+        let creator = Pubkey::new_unique();
+        state.add_contribution(&creator, MIN_FORK_CONTRIBUTION).unwrap();
+
+        //Existing contributors can add any amount, new ones have to pay at least the minimum
+        state.add_contribution(&creator, 1).unwrap();
+        assert!(state.add_contribution(&Pubkey::new_unique(), MIN_FORK_CONTRIBUTION-1).is_err());
+        assert_eq!(state.get_contributors().len(), 1);
+        assert_eq!(state.get_contributors()[0].lamports, MIN_FORK_CONTRIBUTION+1);
+
+        for _ in 1..MAX_FORK_CONTRIBUTORS {
+            state.add_contribution(&Pubkey::new_unique(), MIN_FORK_CONTRIBUTION).unwrap();
+        }
+        assert!(state.add_contribution(&Pubkey::new_unique(), MIN_FORK_CONTRIBUTION).is_err());
+        assert_eq!(state.get_contributors().len(), MAX_FORK_CONTRIBUTORS);
+        state.add_contribution(&creator, 1).unwrap();
+    }
+
+    #[test]
+    fn fork_stale() {
+        let (mut state, _) = main_state(110);
+        state.chain_work = U256::from_u64(1000);
+
+        let mut fork: ForkState = bytemuck::Zeroable::zeroed();
+        fork.start_height = 105;
+        fork.last_submission = 50_000;
+        fork.tip_chain_work = U256::from_u64(1000);
+
+        //Abandoned fork without more chain work than the main chain
+        assert!(!fork.is_stale(&state, 50_000+STALE_FORK_TIMEOUT-1));
+        assert!(fork.is_stale(&state, 50_000+STALE_FORK_TIMEOUT));
+        fork.tip_chain_work = U256::from_u64(1001);
+        assert!(!fork.is_stale(&state, 50_000+STALE_FORK_TIMEOUT));
+
+        //Fork's start pruned from the ring buffer
+        state.block_height = 115;
+        assert!(fork.is_stale(&state, 50_000));
     }
 
 }
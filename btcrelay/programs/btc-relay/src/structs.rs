@@ -1,7 +1,8 @@
 use anchor_lang::{
     prelude::*,
-    solana_program::hash
+    solana_program::{hash, program::get_return_data}
 };
+use crate::errors::*;
 
 //Struct representing bitcoin block header
 //https://www.oreilly.com/library/view/mastering-bitcoin/9781491902639/ch07.html#block_header
@@ -26,6 +27,59 @@ impl BlockHeader {
 
 }
 
+//Bitcoin block header along with its blockhash, which is computed only once, when the blockheader is decoded
+#[derive(Debug, Clone, Copy)]
+pub struct HashedBlockHeader {
+    pub header: BlockHeader, //Bitcoin blockheader
+    pub block_hash: [u8; 32] //Double sha256 of the blockheader
+}
+
+impl HashedBlockHeader {
+
+    pub fn new(header: BlockHeader) -> Result<Self> {
+        Ok(HashedBlockHeader {
+            block_hash: header.get_block_hash()?,
+            header
+        })
+    }
+
+    //Parses the raw bitcoin serialized 80-byte blockheader, hashing the raw data directly, borsh serialization
+    // of the BlockHeader struct matches the bitcoin serialization, so no byte order conversion is needed
+    pub fn from_raw(raw_header: &[u8]) -> Result<Self> {
+        Ok(HashedBlockHeader {
+            header: BlockHeader::try_from_slice(raw_header)?,
+            block_hash: hash::hash(&hash::hash(raw_header).to_bytes()).to_bytes()
+        })
+    }
+
+}
+
+//Struct representing compact bitcoin block header, with the fields derivable from the previous blockheader omitted
+#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize, Copy)]
+pub struct CompactBlockHeader {
+    pub version: u32, //A version number to track software/protocol upgrades
+    pub merkle_root: [u8; 32], //A hash of the root of the merkle tree of this block’s transactions
+    pub timestamp: u32, //The approximate creation time of this block (seconds from Unix Epoch)
+    pub nbits: Option<u32>, //The proof-of-work algorithm difficulty target for this block, None if same as the previous block's
+    pub nonce: u32 //A counter used for the proof-of-work algorithm
+}
+
+impl CompactBlockHeader {
+
+    //Reconstructs the full blockheader, reversed_prev_blockhash is the blockhash of the previous blockheader
+    pub fn to_block_header(&self, reversed_prev_blockhash: [u8; 32], prev_nbits: u32) -> BlockHeader {
+        BlockHeader {
+            version: self.version,
+            reversed_prev_blockhash,
+            merkle_root: self.merkle_root,
+            timestamp: self.timestamp,
+            nbits: self.nbits.unwrap_or(prev_nbits),
+            nonce: self.nonce
+        }
+    }
+
+}
+
 //Struct representing committed block header - bitcoin block header with additional data
 #[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize, Copy)]
 pub struct CommittedBlockHeader {
@@ -36,7 +90,12 @@ pub struct CommittedBlockHeader {
     pub last_diff_adjustment: u32, //Timestamp of the last difficulty adjustment block, used for difficulty retargetting
     pub blockheight: u32, //Block's height
 
-    pub prev_block_timestamps: [u32; 10] //Timestamps of the 10 previous blockheaders, used to calculate median block timestamp
+    pub prev_block_timestamps: [u32; 10], //Timestamps of the 10 previous blockheaders, used to calculate median block timestamp
+
+    //nBits of the last block that wasn't mined using the testnet minimum-difficulty exception,
+    // or of the first block in the current difficulty period, whichever is more recent
+    #[cfg(any(feature = "bitcoin_testnet", feature = "bitcoin_testnet4"))]
+    pub last_non_min_nbits: u32
 }
 
 impl CommittedBlockHeader {
@@ -48,4 +107,137 @@ impl CommittedBlockHeader {
         Ok(hash::hash(&arr).to_bytes())
     }
 
-}
\ No newline at end of file
+    //Returns the median time past (BIP113) of the block, that is the median timestamp of the block and 10 previous blocks
+    pub fn get_median_time_past(&self) -> u32 {
+        let mut timestamps = [0u32; 11];
+        timestamps[..10].copy_from_slice(&self.prev_block_timestamps);
+        timestamps[10] = self.header.timestamp;
+        timestamps.sort_unstable();
+        timestamps[5]
+    }
+
+    //Returns nBits of the last block that wasn't mined using the minimum-difficulty exception,
+    // on networks without the exception this is always the block's own nBits
+    pub fn get_last_non_min_nbits(&self) -> u32 {
+        #[cfg(any(feature = "bitcoin_testnet", feature = "bitcoin_testnet4"))]
+        return self.last_non_min_nbits;
+        #[cfg(not(any(feature = "bitcoin_testnet", feature = "bitcoin_testnet4")))]
+        return self.header.nbits;
+    }
+
+}
+
+//Metadata of the block containing the transaction verified by verify_transaction, set as the instruction's return data
+#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize, Copy)]
+pub struct VerifiedBlock {
+    pub block_height: u32, //Block's height
+    pub block_hash: [u8; 32], //Blockhash of the block
+    pub timestamp: u32, //Block's timestamp
+    pub median_time_past: u32, //Median time past (BIP113) of the block
+    pub confirmations: u32 //Current number of confirmations of the block
+}
+
+impl VerifiedBlock {
+
+    pub fn new(commited_header: &CommittedBlockHeader, confirmations: u32) -> Result<Self> {
+        Ok(VerifiedBlock {
+            block_height: commited_header.blockheight,
+            block_hash: commited_header.header.get_block_hash()?,
+            timestamp: commited_header.header.timestamp,
+            median_time_past: commited_header.get_median_time_past(),
+            confirmations
+        })
+    }
+
+    //Decodes the block metadata from the return data, to be used by CPI callers right after the verify_transaction CPI
+    pub fn from_return_data() -> Result<Self> {
+        let (program_id, data) = get_return_data().ok_or(RelayErrorCode::InvalidReturnData)?;
+        require!(
+            program_id == crate::ID,
+            RelayErrorCode::InvalidReturnData
+        );
+        Ok(VerifiedBlock::try_from_slice(&data)?)
+    }
+
+}
+
+//Transaction output verified by verify_output_payment, set as the instruction's return data
+#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize, Copy)]
+pub struct VerifiedOutput {
+    pub block: VerifiedBlock, //Metadata of the transaction's block
+    pub reversed_txid: [u8; 32], //Transaction ID of the transaction
+    pub vout: u32, //Index of the output in the transaction
+    pub value: u64, //Output value in satoshis
+    pub script_hash: [u8; 32] //sha256 hash of the output script
+}
+
+impl VerifiedOutput {
+
+    //Decodes the verified output from the return data, to be used by CPI callers right after the verify_output_payment CPI
+    pub fn from_return_data() -> Result<Self> {
+        let (program_id, data) = get_return_data().ok_or(RelayErrorCode::InvalidReturnData)?;
+        require!(
+            program_id == crate::ID,
+            RelayErrorCode::InvalidReturnData
+        );
+        Ok(VerifiedOutput::try_from_slice(&data)?)
+    }
+
+}
+
+//Segwit transaction input's witness verified by verify_raw_witness_transaction, set as the instruction's return data
+#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct VerifiedWitness {
+    pub block: VerifiedBlock, //Metadata of the transaction's block
+    pub reversed_txid: [u8; 32], //Transaction ID of the transaction
+    pub reversed_wtxid: [u8; 32], //Witness transaction ID of the transaction
+    pub input_index: u32, //Index of the input in the transaction
+    pub witness: Vec<Vec<u8>> //Witness stack of the input
+}
+
+impl VerifiedWitness {
+
+    //Decodes the verified witness from the return data, to be used by CPI callers right after the verify_raw_witness_transaction CPI
+    pub fn from_return_data() -> Result<Self> {
+        let (program_id, data) = get_return_data().ok_or(RelayErrorCode::InvalidReturnData)?;
+        require!(
+            program_id == crate::ID,
+            RelayErrorCode::InvalidReturnData
+        );
+        Ok(VerifiedWitness::try_from_slice(&data)?)
+    }
+
+}
+
+//Partial merkle tree (as in bitcoin's merkleblock message) proving inclusion of multiple transactions in a single block
+#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct BlockTransactionsProof {
+    pub header_index: u32, //Index of the block's committed header in the headers passed to the instruction
+    pub total_transactions: u32, //Number of transactions in the block
+    pub reversed_hashes: Vec<[u8; 32]>, //Hashes of the tree nodes in depth-first order
+    pub flags: Vec<u8>, //Flag bits of the tree nodes in depth-first order, packed with the least significant bit first
+    pub reversed_txids: Vec<[u8; 32]> //Txids of the transactions to be verified, in the order of their position in the block
+}
+
+//Struct representing coinbase transaction of a block along with its merkle proof
+#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct CoinbaseProof {
+    pub transaction: Vec<u8>, //Coinbase transaction data, with the witness data stripped
+    pub reversed_merkle_proof: Vec<[u8; 32]> //Merkle proof of the coinbase transaction (always at index 0)
+}
+
+//Struct representing signet block header along with the coinbase transaction containing the block solution
+#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct SignetBlockHeader {
+    pub header: BlockHeader, //Bitcoin blockheader
+    pub coinbase: CoinbaseProof //Coinbase transaction of the block, containing signet block solution
+}
+
+//Blockheaders passed to the header submission instructions, in one of the supported encodings
+#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize)]
+pub enum HeadersData {
+    Borsh(Vec<BlockHeader>), //Borsh serialized BlockHeader structs
+    Raw(Vec<u8>), //Raw bitcoin serialized 80-byte blockheaders (as returned by bitcoind's getblockheader with verbose=false) concatenated together
+    Compact(Vec<CompactBlockHeader>), //Compact blockheaders, omitting the previous blockhash and (optionally) nBits
+    Signet(Vec<SignetBlockHeader>) //Signet blockheaders, each accompanied by its coinbase transaction containing the block solution
+}
@@ -0,0 +1,445 @@
+//Unsigned 256-bit integer stored as 4 little endian u64 limbs (limb 0 being the least significant),
+// used for difficulty target & chain work arithmetic
+//U256 can be used directly in zero copy accounts & borsh serialized structs, in both cases it is laid out as its limbs,
+// that is a 32-byte little endian integer, chain work committed in CommittedBlockHeader is still stored as 32-byte
+// big endian array (not to change the block commitments), conversion is done with from_be_bytes & to_be_bytes
+use anchor_lang::prelude::*;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Shl, Shr, Sub};
+
+#[zero_copy]
+#[derive(Default, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub struct U256 {
+    pub limbs: [u64; 4]
+}
+
+impl U256 {
+
+    pub const ZERO: U256 = U256 { limbs: [0; 4] };
+
+    pub const fn from_u64(value: u64) -> Self {
+        U256 { limbs: [value, 0, 0, 0] }
+    }
+
+    pub const fn from_be_bytes(bytes: [u8; 32]) -> Self {
+        let mut limbs = [0u64; 4];
+        let mut i = 0;
+        while i < 32 {
+            limbs[3 - i/8] |= (bytes[i] as u64) << ((7 - i%8)*8);
+            i += 1;
+        }
+        U256 { limbs }
+    }
+
+    pub const fn from_le_bytes(bytes: [u8; 32]) -> Self {
+        let mut limbs = [0u64; 4];
+        let mut i = 0;
+        while i < 32 {
+            limbs[i/8] |= (bytes[i] as u64) << ((i%8)*8);
+            i += 1;
+        }
+        U256 { limbs }
+    }
+
+    pub fn to_be_bytes(self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.limbs.iter().rev().enumerate() {
+            bytes[i*8..(i+1)*8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    //Returns the least significant 64 bits
+    pub fn low_u64(&self) -> u64 {
+        self.limbs[0]
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs == [0; 4]
+    }
+
+    //Number of bits needed to represent the value, 0 for zero
+    pub fn bits(&self) -> u32 {
+        256 - self.leading_zeros()
+    }
+
+    pub fn leading_zeros(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.limbs[i] != 0 {
+                return (3-i as u32)*64 + self.limbs[i].leading_zeros();
+            }
+        }
+        256
+    }
+
+    pub fn trailing_zeros(&self) -> u32 {
+        for i in 0..4 {
+            if self.limbs[i] != 0 {
+                return i as u32*64 + self.limbs[i].trailing_zeros();
+            }
+        }
+        256
+    }
+
+    pub fn overflowing_add(self, other: U256) -> (U256, bool) {
+        let mut result = [0u64; 4];
+        let mut carry = false;
+        #[allow(clippy::needless_range_loop)]
+        for i in 0..4 {
+            let (sum, carry1) = self.limbs[i].overflowing_add(other.limbs[i]);
+            let (sum, carry2) = sum.overflowing_add(carry as u64);
+            result[i] = sum;
+            carry = carry1 || carry2;
+        }
+        (U256 { limbs: result }, carry)
+    }
+
+    pub fn overflowing_sub(self, other: U256) -> (U256, bool) {
+        let mut result = [0u64; 4];
+        let mut borrow = false;
+        #[allow(clippy::needless_range_loop)]
+        for i in 0..4 {
+            let (diff, borrow1) = self.limbs[i].overflowing_sub(other.limbs[i]);
+            let (diff, borrow2) = diff.overflowing_sub(borrow as u64);
+            result[i] = diff;
+            borrow = borrow1 || borrow2;
+        }
+        (U256 { limbs: result }, borrow)
+    }
+
+    //Full width multiplication, returns the lower 256 bits of the product and whether the product overflowed
+    pub fn overflowing_mul(self, other: U256) -> (U256, bool) {
+        let mut result = [0u64; 8];
+        for i in 0..4 {
+            if self.limbs[i] == 0 {
+                continue;
+            }
+            let mut carry: u128 = 0;
+            for j in 0..4 {
+                let product = (self.limbs[i] as u128)*(other.limbs[j] as u128) + result[i+j] as u128 + carry;
+                result[i+j] = product as u64;
+                carry = product >> 64;
+            }
+            result[i+4] = carry as u64;
+        }
+        let overflow = result[4..].iter().any(|limb| *limb != 0);
+        (U256 { limbs: [result[0], result[1], result[2], result[3]] }, overflow)
+    }
+
+    pub fn checked_add(self, other: U256) -> Option<U256> {
+        match self.overflowing_add(other) {
+            (result, false) => Some(result),
+            _ => None
+        }
+    }
+
+    pub fn checked_sub(self, other: U256) -> Option<U256> {
+        match self.overflowing_sub(other) {
+            (result, false) => Some(result),
+            _ => None
+        }
+    }
+
+    pub fn checked_mul(self, other: U256) -> Option<U256> {
+        match self.overflowing_mul(other) {
+            (result, false) => Some(result),
+            _ => None
+        }
+    }
+
+    //Division by a divisor fitting in u64, returns the quotient and the remainder
+    fn div_rem_u64(self, divisor: u64) -> (U256, u64) {
+        let mut result = [0u64; 4];
+        let mut remainder: u128 = 0;
+        for i in (0..4).rev() {
+            let value = (remainder << 64) | self.limbs[i] as u128;
+            result[i] = (value / divisor as u128) as u64;
+            remainder = value % divisor as u128;
+        }
+        (U256 { limbs: result }, remainder as u64)
+    }
+
+    //Full width division, returns the quotient and the remainder, None if the divisor is zero
+    pub fn checked_div_rem(self, divisor: U256) -> Option<(U256, U256)> {
+        if divisor.is_zero() {
+            return None;
+        }
+        if divisor.bits() <= 64 {
+            let (quotient, remainder) = self.div_rem_u64(divisor.low_u64());
+            return Some((quotient, U256::from_u64(remainder)));
+        }
+        if self < divisor {
+            return Some((U256::ZERO, self));
+        }
+
+        //Binary long division, only iterating over the bit length difference
+        let shift = divisor.leading_zeros() - self.leading_zeros();
+        let mut shifted_divisor = divisor << shift;
+        let mut quotient = U256::ZERO;
+        let mut remainder = self;
+        for i in (0..=shift).rev() {
+            if remainder >= shifted_divisor {
+                remainder = remainder - shifted_divisor;
+                quotient.limbs[(i/64) as usize] |= 1 << (i%64);
+            }
+            shifted_divisor = shifted_divisor >> 1;
+        }
+        Some((quotient, remainder))
+    }
+
+    //Full width division, None if the divisor is zero
+    pub fn checked_div(self, divisor: U256) -> Option<U256> {
+        if divisor.is_zero() {
+            return None;
+        }
+
+        //Trailing zeros of the divisor can be shifted out of both operands without affecting the quotient,
+        // this makes division by targets (having at most 23 significant bits) use the fast u64 path
+        let trailing_zeros = divisor.trailing_zeros();
+        (self >> trailing_zeros).checked_div_rem(divisor >> trailing_zeros).map(|(quotient, _)| quotient)
+    }
+
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for i in (0..4).rev() {
+            match self.limbs[i].cmp(&other.limbs[i]) {
+                Ordering::Equal => continue,
+                ordering => return ordering
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+//Arithmetic operators panic on overflow & division by zero, same as primitive integers with overflow checks enabled
+impl Add for U256 {
+    type Output = U256;
+
+    fn add(self, other: U256) -> U256 {
+        self.checked_add(other).expect("U256 addition overflow")
+    }
+}
+
+impl Sub for U256 {
+    type Output = U256;
+
+    fn sub(self, other: U256) -> U256 {
+        self.checked_sub(other).expect("U256 subtraction overflow")
+    }
+}
+
+impl Mul for U256 {
+    type Output = U256;
+
+    fn mul(self, other: U256) -> U256 {
+        self.checked_mul(other).expect("U256 multiplication overflow")
+    }
+}
+
+impl Div for U256 {
+    type Output = U256;
+
+    fn div(self, other: U256) -> U256 {
+        self.checked_div(other).expect("U256 division by zero")
+    }
+}
+
+impl Shl<u32> for U256 {
+    type Output = U256;
+
+    fn shl(self, shift: u32) -> U256 {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        let limb_shift = (shift/64) as usize;
+        let bit_shift = shift%64;
+        let mut result = [0u64; 4];
+        #[allow(clippy::needless_range_loop)]
+        for i in limb_shift..4 {
+            result[i] = self.limbs[i-limb_shift] << bit_shift;
+            if bit_shift > 0 && i > limb_shift {
+                result[i] |= self.limbs[i-limb_shift-1] >> (64-bit_shift);
+            }
+        }
+        U256 { limbs: result }
+    }
+}
+
+impl Shr<u32> for U256 {
+    type Output = U256;
+
+    fn shr(self, shift: u32) -> U256 {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        let limb_shift = (shift/64) as usize;
+        let bit_shift = shift%64;
+        let mut result = [0u64; 4];
+        #[allow(clippy::needless_range_loop)]
+        for i in 0..4-limb_shift {
+            result[i] = self.limbs[i+limb_shift] >> bit_shift;
+            if bit_shift > 0 && i+limb_shift < 3 {
+                result[i] |= self.limbs[i+limb_shift+1] << (64-bit_shift);
+            }
+        }
+        U256 { limbs: result }
+    }
+}
+
+impl fmt::LowerHex for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for limb in self.limbs.iter().rev() {
+            write!(f, "{:016x}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:x}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: U256 = U256 { limbs: [u64::MAX; 4] };
+
+    fn from_u128(value: u128) -> U256 {
+        U256 { limbs: [value as u64, (value >> 64) as u64, 0, 0] }
+    }
+
+    //Checks the quotient & remainder against the division identity
+    fn check_div_rem(dividend: U256, divisor: U256) {
+        let (quotient, remainder) = dividend.checked_div_rem(divisor).unwrap();
+        assert!(remainder < divisor);
+        assert_eq!(quotient.checked_mul(divisor).unwrap().checked_add(remainder).unwrap(), dividend);
+        assert_eq!(dividend.checked_div(divisor).unwrap(), quotient);
+    }
+
+    #[test]
+    fn div_rem_u64_divisor() {
+        let (quotient, remainder) = from_u128(1_000_000_007u128 * 998_244_353 + 12345).checked_div_rem(U256::from_u64(998_244_353)).unwrap();
+        assert_eq!(quotient, U256::from_u64(1_000_000_007));
+        assert_eq!(remainder, U256::from_u64(12345));
+
+        check_div_rem(MAX, U256::from_u64(1));
+        check_div_rem(MAX, U256::from_u64(u64::MAX));
+        check_div_rem(MAX, U256::from_u64(0x00ffff));
+        check_div_rem(U256::from_u64(5), U256::from_u64(7));
+        assert_eq!(U256::from_u64(5).checked_div_rem(U256::ZERO), None);
+        assert_eq!(U256::from_u64(5).checked_div(U256::ZERO), None);
+    }
+
+    #[test]
+    fn div_rem_long_division() {
+        let (quotient, remainder) = from_u128(u128::MAX).checked_div_rem(from_u128(1u128 << 64)).unwrap();
+        assert_eq!(quotient, U256::from_u64(u64::MAX));
+        assert_eq!(remainder, U256::from_u64(u64::MAX));
+
+        check_div_rem(MAX, from_u128(1u128 << 64));
+        check_div_rem(MAX, from_u128(u128::MAX));
+        check_div_rem(MAX, MAX);
+        check_div_rem(MAX, MAX >> 1);
+        check_div_rem(U256 { limbs: [0x0123456789abcdef, 0xfedcba9876543210, 0x0f1e2d3c4b5a6978, 0x8796a5b4c3d2e1f0] }, U256 { limbs: [0x1111, 0x2222, 0x3333, 0] });
+        check_div_rem(from_u128(1u128 << 100), MAX);
+
+        //Divisor with trailing zeros (e.g. a difficulty target) uses the fast path after shifting
+        assert_eq!(MAX.checked_div(U256::from_u64(0xffff) << 208), Some(U256::from_u64(0x100010001)));
+    }
+
+    #[test]
+    fn shifts() {
+        let value = U256 { limbs: [0x8000000000000001, 0x8000000000000001, 0x8000000000000001, 0x8000000000000001] };
+        assert_eq!(value << 0, value);
+        assert_eq!(value >> 0, value);
+
+        assert_eq!(value << 63, U256 { limbs: [0x8000000000000000, 0xc000000000000000, 0xc000000000000000, 0xc000000000000000] });
+        assert_eq!(value >> 63, U256 { limbs: [0x0000000000000003, 0x0000000000000003, 0x0000000000000003, 0x0000000000000001] });
+
+        assert_eq!(value << 64, U256 { limbs: [0, 0x8000000000000001, 0x8000000000000001, 0x8000000000000001] });
+        assert_eq!(value >> 64, U256 { limbs: [0x8000000000000001, 0x8000000000000001, 0x8000000000000001, 0] });
+
+        assert_eq!(value << 65, U256 { limbs: [0, 0x0000000000000002, 0x0000000000000003, 0x0000000000000003] });
+        assert_eq!(value >> 65, U256 { limbs: [0xc000000000000000, 0xc000000000000000, 0x4000000000000000, 0] });
+
+        assert_eq!(value << 255, U256 { limbs: [0, 0, 0, 0x8000000000000000] });
+        assert_eq!(value >> 255, U256::from_u64(1));
+
+        assert_eq!(value << 256, U256::ZERO);
+        assert_eq!(value >> 256, U256::ZERO);
+    }
+
+    #[test]
+    fn mul_carry() {
+        //Carry between the limbs
+        assert_eq!(U256::from_u64(u64::MAX).overflowing_mul(U256::from_u64(u64::MAX)), (from_u128(u64::MAX as u128 * u64::MAX as u128), false));
+        assert_eq!(from_u128(u128::MAX).overflowing_mul(from_u128(u128::MAX)), (U256 { limbs: [1, 0, u64::MAX - 1, u64::MAX] }, false));
+
+        //Overflow past 256 bits
+        assert_eq!(from_u128(1u128 << 127).overflowing_mul(from_u128(1u128 << 127)), (U256 { limbs: [0, 0, 0, 1 << 62] }, false));
+        assert_eq!(from_u128(1u128 << 127).overflowing_mul(from_u128(1u128 << 127) << 2), (U256::ZERO, true));
+        assert_eq!(MAX.overflowing_mul(MAX), (U256::from_u64(1), true));
+        assert_eq!(MAX.checked_mul(U256::from_u64(2)), None);
+        assert_eq!(MAX.checked_mul(U256::from_u64(1)), Some(MAX));
+
+        assert_eq!(MAX.checked_add(U256::from_u64(1)), None);
+        assert_eq!(U256::ZERO.checked_sub(U256::from_u64(1)), None);
+        assert_eq!(from_u128(u64::MAX as u128) + U256::from_u64(1), from_u128(1u128 << 64));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let value = U256::from_be_bytes(bytes);
+        assert_eq!(value.limbs[3], 0x0001020304050607);
+        assert_eq!(value.limbs[0], 0x18191a1b1c1d1e1f);
+        assert_eq!(value.to_be_bytes(), bytes);
+
+        let mut reversed = bytes;
+        reversed.reverse();
+        assert_eq!(U256::from_le_bytes(reversed), value);
+
+        assert_eq!(U256::from_be_bytes(MAX.to_be_bytes()), MAX);
+        assert_eq!(U256::ZERO.to_be_bytes(), [0u8; 32]);
+    }
+
+    #[test]
+    fn bit_counts() {
+        assert_eq!(U256::ZERO.bits(), 0);
+        assert_eq!(U256::ZERO.trailing_zeros(), 256);
+        assert_eq!(MAX.bits(), 256);
+        assert_eq!((U256::from_u64(1) << 200).bits(), 201);
+        assert_eq!((U256::from_u64(1) << 200).trailing_zeros(), 200);
+    }
+
+    #[test]
+    fn account_layout() {
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let value = U256::from_le_bytes(bytes);
+
+        //Zero copy & borsh layouts are both the 32-byte little endian integer
+        assert_eq!(bytemuck::bytes_of(&value), &bytes);
+        assert_eq!(*bytemuck::from_bytes::<U256>(&bytes), value);
+        assert_eq!(value.try_to_vec().unwrap(), bytes.to_vec());
+        assert_eq!(U256::try_from_slice(&bytes).unwrap(), value);
+    }
+}
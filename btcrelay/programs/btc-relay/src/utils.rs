@@ -1,260 +1,158 @@
 //Utilities for blockheader verification
 use anchor_lang::{
     prelude::*,
+    system_program,
     solana_program::clock,
     solana_program::hash
 };
 use crate::errors::*;
 use crate::structs::*;
-use crate::arrayutils;
+use crate::u256::U256;
+use crate::btctx;
+use crate::signet;
+use crate::consensus::{PARAMS, MAX_TIMEWARP};
+
+//Size of the serialized bitcoin blockheader
+pub const BLOCK_HEADER_SIZE: usize = 80;
 
 //Returns current timestamp read from Solana's on-chain clock
 pub fn now_ts() -> Result<u32> {
     Ok(clock::Clock::get()?.unix_timestamp.try_into().unwrap())
 }
 
-//https://en.bitcoin.it/wiki/Difficulty#How_is_difficulty_calculated.3F_What_is_the_difference_between_bdiff_and_pdiff.3F
-const MAX_DIFFICULTY: [u8; 32] = [
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8
-];
-
-//https://en.bitcoin.it/wiki/Target#What_is_the_maximum_target.3F
-const UNROUNDED_MAX_TARGET: [u8; 32] = [
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0x00_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8,
-    0xFF_u8
-];
-
-//Bitcoin constants
-const DIFF_ADJUSTMENT_INTERVAL: u32 = 2016;
-const TARGET_TIMESPAN: u32 = 14 * 24 * 60 * 60; // 2 weeks
-
-//Pre-calculated multiples for target timespan
-const TARGET_TIMESPAN_DIV_4: u32 = TARGET_TIMESPAN / 4;
-const TARGET_TIMESPAN_MUL_4: u32 = TARGET_TIMESPAN * 4;
-
-//Maximum positive difference between bitcoin block's timestamp and Solana's on-chain clock
-//Nodes in bitcoin network generally reject any block with timestamp more than 2 hours in the future
-//As we are dealing with another blockchain here,
-// with the possibility of the Solana's on-chain clock being skewed, we chose double the value - 4 hours
-const MAX_FUTURE_BLOCKTIME: u32 = 4 * 60 * 60;
+//Reallocates the account to the new length, topping up its rent exemption from the payer
+pub fn realloc_account<'info>(account: &AccountInfo<'info>, payer: &Signer<'info>, system_program: &Program<'info, System>, new_len: usize) -> Result<()> {
+    let required_lamports = Rent::get()?.minimum_balance(new_len);
+    let starting_lamports = account.lamports();
+    if starting_lamports < required_lamports {
+        let transfer_lamports_instruction = system_program::Transfer {
+            from: payer.to_account_info(),
+            to: account.clone()
+        };
+        let cpi_ctx = CpiContext::new(system_program.to_account_info(), transfer_lamports_instruction);
+        system_program::transfer(cpi_ctx, required_lamports-starting_lamports)?;
+    }
+
+    account.realloc(new_len, true)?;
+
+    Ok(())
+}
 
 //Compresses difficulty target to nBits
 //Description: https://btcinformation.org/en/developer-reference#target-nbits
-pub fn target_to_nbits(target: [u8; 32]) -> u32 {
-
-    let mut n_size: u32 = 0;
-    #[allow(clippy::needless_range_loop)]
-    for i in 0..32 {
-        if target[i]>0 {
-            n_size = (32-i) as u32;
-            break;
-        }
-    }
+pub fn target_to_nbits(target: U256) -> u32 {
+    let mut n_size = target.bits().div_ceil(8);
 
-    let mut n_compact: u32 = 0;
+    //Mantissa are the 3 most significant bytes of the target
+    let mut n_compact = if n_size <= 3 {
+        (target.low_u64() << (8*(3-n_size))) as u32
+    } else {
+        (target >> (8*(n_size-3))).low_u64() as u32
+    };
 
-    for i in 0..3 {
-        let pos = (32-n_size+i) as usize;
-        if pos<32 {
-            n_compact |= (target[pos] as u32) << ((2-i)*8);
-        }
-    }
-    
+    //Mantissa's highest bit is the sign bit, so it has to be shifted away
     if (n_compact & 0x00800000) > 0 {
         n_compact >>= 8;
         n_size += 1;
     }
 
-    n_compact = n_compact & 0x00FFFFFF |
-                (n_size<<24) & 0xFF000000;
-
-    n_compact
-
+    n_compact & 0x00FFFFFF | (n_size<<24) & 0xFF000000
 }
 
 //Calculates difficulty target from nBits
 //Description: https://btcinformation.org/en/developer-reference#target-nbits
-pub fn nbits_to_target(nbits: u32) -> [u8; 32] {
-    let mut target: [u8; 32] = [0; 32];
-
+pub fn nbits_to_target(nbits: u32) -> U256 {
     let n_size = (nbits>>24) & 0xFF;
+    let n_word = U256::from_u64((nbits & 0x007FFFFF) as u64);
 
-    let n_word: [u8; 3] = [
-        ((nbits >> 16) & 0x7F) as u8,
-        ((nbits >> 8) & 0xFF) as u8,
-        ((nbits) & 0xFF) as u8,
-    ];
-
-    let start = (32-n_size) as usize;
-    for i in 0..3 {
-        if start+i<32 {
-            target[start+i] = n_word[i];
-        }
+    if n_size <= 3 {
+        n_word >> (8*(3-n_size))
+    } else {
+        n_word << (8*(n_size-3))
     }
-
-    target
 }
 
 //Calculates difficulty from difficulty target
 //https://en.bitcoin.it/wiki/Difficulty#How_is_difficulty_calculated.3F_What_is_the_difference_between_bdiff_and_pdiff.3F
 // difficulty = MAX_DIFFICULTY/target
-pub fn get_difficulty(target: [u8; 32]) -> [u8; 32] {
-
-    //Find leading byte (first non-zero byte)
-    let mut start = 0;
-    
-    #[allow(clippy::needless_range_loop)]
-    for i in 0..32 {
-        if target[i]>0 {
-            start = i;
-            break
-        }
-    }
-
-    let shift = 32 - start - 3;
-
-    //Target calculated from nBits will only ever have 3 bytes set,
-    // preceeded and followed by zeroes 00 00 00 ... A1 D0 21 ... 00 00
-    //We extract these first 3 bytes here to a u32,
-    // essentially doing a floor division on the target:
-    // num = target//(2^shift)
-    let mut num: u32 = 0;
-    for i in 0..3 {
-        num |= (target[start+i] as u32) << ((2-i)*8);
-    }
-
-    //Do the division
-    // arr = MAX_DIFFICULTY//num
-    let mut arr: [u8; 32] = MAX_DIFFICULTY;
-    arrayutils::div_in_place(&mut arr, num);
-
-    let mut result: [u8; 32] = [0;32];
-
-    //Shift the result back:
-    // result = arr//(2^shift)
-    #[allow(clippy::manual_memcpy)]
-    for i in 0..(32-shift) {
-        result[i+shift] = arr[i];
-    }
-
-    //Result
-    // result = (MAX_DIFFICULTY // (target // (2^shift))) // (2^shift)
-    // result = ((MAX_DIFFICULTY * (2^shift)) // target) // (2^shift)
-    // result = (MAX_DIFFICULTY * (2^shift)) // (target * (2^shift))
-    // result = MAX_DIFFICULTY // target
-    result
-
+pub fn get_difficulty(target: U256) -> U256 {
+    PARAMS.max_difficulty / target
 }
 
 //Difficulty retargetting algorithm
 //https://minerdaily.com/2021/how-are-bitcoins-difficulty-and-hash-rate-calculated/#Difficulty_Adjustments
 // new_difficulty_target = prev_difficulty_target * (timespan / target_timespan)
-pub fn compute_new_nbits(prev_time: u32, start_time: u32, prev_target: &mut [u8; 32]) -> u32 {
+pub fn compute_new_nbits(prev_time: u32, start_time: u32, prev_target: U256) -> u32 {
 
     let mut time_span = prev_time - start_time;
 
     //Difficulty increase/decrease multiples are clamped between 0.25 (-75%) and 4 (+300%)
-    if time_span < TARGET_TIMESPAN_DIV_4 {
-        time_span = TARGET_TIMESPAN_DIV_4;
+    if time_span < PARAMS.target_timespan / 4 {
+        time_span = PARAMS.target_timespan / 4;
     }
-    if time_span > TARGET_TIMESPAN_MUL_4 {
-        time_span = TARGET_TIMESPAN_MUL_4;
+    if time_span > PARAMS.target_timespan * 4 {
+        time_span = PARAMS.target_timespan * 4;
     }
 
-    arrayutils::mul_in_place(prev_target, time_span);
-    arrayutils::div_in_place(prev_target, TARGET_TIMESPAN);
-
-    //Check if the target isn't past maximum allowed target (lowest possible mining difficulty)
+    //Check if the target isn't past maximum allowed target (lowest possible mining difficulty),
+    // overflowing target is way past the maximum target
     //https://en.bitcoin.it/wiki/Target#What_is_the_maximum_target.3F
-    if arrayutils::gt_arr(*prev_target, UNROUNDED_MAX_TARGET) {
-        return target_to_nbits(UNROUNDED_MAX_TARGET);
+    let new_target = match prev_target.checked_mul(U256::from_u64(time_span as u64)) {
+        Some(target) => target / U256::from_u64(PARAMS.target_timespan as u64),
+        None => return PARAMS.pow_limit_nbits
+    };
+    if new_target > PARAMS.pow_limit {
+        return PARAMS.pow_limit_nbits;
     }
 
-    target_to_nbits(*prev_target)
+    target_to_nbits(new_target)
 }
 
 pub fn should_diff_adjust(block_height: u32) -> bool {
-    block_height % DIFF_ADJUSTMENT_INTERVAL == 0
+    block_height % PARAMS.diff_adjustment_interval == 0
 }
 
 //Checks difficulty target (nBits) specified in the block,
-// handles difficulty adjustmens happening every DIFF_ADJUSTMENT_INTERVAL blocks
-pub fn has_correct_difficulty_target(prev_committed_header: CommittedBlockHeader, current_nbits: u32) -> bool {
-    let prev_nbits = prev_committed_header.header.nbits;
-
-    if should_diff_adjust(prev_committed_header.blockheight+1) {
-        let mut prev_target = nbits_to_target(prev_nbits);
+// handles difficulty adjustmens happening every diff_adjustment_interval blocks
+pub fn has_correct_difficulty_target(prev_committed_header: CommittedBlockHeader, header: &BlockHeader) -> bool {
+    let current_nbits = header.nbits;
+
+    if !PARAMS.no_retargeting && should_diff_adjust(prev_committed_header.blockheight+1) {
+        //Testnet3 computes the new target from the nBits of the last block in the period,
+        // even if that block was mined using the minimum-difficulty exception, testnet4 (BIP94)
+        // fixes this by using the nBits of the first block in the period instead
+        let prev_nbits = if PARAMS.enforce_bip94 {
+            prev_committed_header.get_last_non_min_nbits()
+        } else {
+            prev_committed_header.header.nbits
+        };
+        let prev_target = nbits_to_target(prev_nbits);
         let prev_time = prev_committed_header.header.timestamp;
         let start_time = prev_committed_header.last_diff_adjustment;
-        msg!("Prev target: {:x?}", prev_target);
-        let new_nbits = compute_new_nbits(prev_time, start_time, &mut prev_target);
+        msg!("Prev target: {:x}", prev_target);
+        let new_nbits = compute_new_nbits(prev_time, start_time, prev_target);
         msg!("New computed nbits: {:x?}", new_nbits);
-        msg!("New target: {:x?}", prev_target);
-        current_nbits == new_nbits
-    } else {
-        current_nbits == prev_nbits
+        msg!("New target: {:x}", nbits_to_target(new_nbits));
+        return current_nbits == new_nbits;
     }
+
+    //Testnets allow mining a block with minimum difficulty if no block was found
+    // for 20 minutes, otherwise the block has to use the difficulty of the last
+    // block that wasn't mined with the minimum difficulty
+    if PARAMS.allow_min_difficulty_blocks {
+        if header.timestamp > prev_committed_header.header.timestamp + PARAMS.min_difficulty_block_spacing {
+            return current_nbits == PARAMS.pow_limit_nbits;
+        }
+        return current_nbits == prev_committed_header.get_last_non_min_nbits();
+    }
+
+    current_nbits == prev_committed_header.header.nbits
+}
+
+//Returns whether the block can be used as a starting point for the testnet minimum-difficulty
+// walk back, that is it isn't mined using the minimum-difficulty exception
+pub fn is_non_min_difficulty_block(block_height: u32, nbits: u32) -> bool {
+    should_diff_adjust(block_height) || nbits != PARAMS.pow_limit_nbits
 }
 
 //Checks if the timestamp is larger than median of the past block's timestamps (specified in arr and one additional value)
@@ -274,21 +172,16 @@ pub fn is_larger_than_median(arr: [u32; 10], additional: u32, curr_timestamp: u3
     amt>5
 }
 
-pub fn verify_header(header: &BlockHeader, last_commited_header: &mut CommittedBlockHeader, remaining_account: &AccountInfo, _signer: &Signer, program_id: &Pubkey) -> Result<[u8; 32]> {
-    
+//Verifies the blockheader, whose blockhash was already computed when it was decoded, against the last committed header,
+// and updates the last committed header to the verified blockheader
+pub fn verify_header(hashed_header: &HashedBlockHeader, last_commited_header: &mut CommittedBlockHeader, remaining_account: Option<&AccountInfo>, _signer: &Signer, program_id: &Pubkey) -> Result<()> {
+    let header = &hashed_header.header;
+
     //Correct difficulty target
-    //
-    //Should be disabled for testnet, since if no valid block is
-    // found on testnet in 20 minutes, the difficulty drops to 1
-    //Implementing this functionality is beyond scope of this implementation,
-    // so nBits checking is disabled for TESTNET
-    #[cfg(not(feature = "bitcoin_testnet"))]
-    {
-        require!(
-            has_correct_difficulty_target(*last_commited_header, header.nbits),
-            RelayErrorCode::ErrDiffTarget
-        );
-    }
+    require!(
+        has_correct_difficulty_target(*last_commited_header, header),
+        RelayErrorCode::ErrDiffTarget
+    );
     
     //Set last_diff_adjustment if difficulty should be adjusted
     let timestamp = header.timestamp;
@@ -298,20 +191,20 @@ pub fn verify_header(header: &BlockHeader, last_commited_header: &mut CommittedB
     
     //Check if valid topic was specified in remaining accounts
     //Each block is assigned a unique generated PDA,
-    // this is used purely for indexing purposes
-    let last_block_hash = header.get_block_hash()?;
-    let (block_header_topic, _block_header_bump) = Pubkey::find_program_address(&[b"header", &last_block_hash], program_id);
-    require!(
-        block_header_topic == *remaining_account.key,
-        RelayErrorCode::InvalidHeaderTopic
-    );
+    // this is used purely for indexing purposes, so it can be skipped
+    let last_block_hash = hashed_header.block_hash;
+    if let Some(remaining_account) = remaining_account {
+        let (block_header_topic, _block_header_bump) = Pubkey::find_program_address(&[b"header", &last_block_hash], program_id);
+        require!(
+            block_header_topic == *remaining_account.key,
+            RelayErrorCode::InvalidHeaderTopic
+        );
+    }
 
-    //Check block's PoW, it's hash has to be less than the target
-    let mut block_hash = last_block_hash;
-    block_hash.reverse();
+    //Check block's PoW, it's hash (little endian) has to be less than the target
     let target = nbits_to_target(header.nbits);
     require!(
-        arrayutils::lte_arr(block_hash, target),
+        U256::from_le_bytes(last_block_hash) <= target,
         RelayErrorCode::ErrPowToolow
     );
 
@@ -323,14 +216,23 @@ pub fn verify_header(header: &BlockHeader, last_commited_header: &mut CommittedB
         RelayErrorCode::ErrTimestampToolow
     );
 
-    let current_timestamp = now_ts()?;
-
-    //Verify timestamp is no more than MAX_FUTURE_BLOCKTIME in the future
+    //Verify timestamp of the first block in the difficulty period isn't too far
+    // before the previous block's timestamp (BIP94 timewarp protection)
     require!(
-        timestamp < current_timestamp+MAX_FUTURE_BLOCKTIME,
-        RelayErrorCode::ErrTimestampTooHigh
+        !PARAMS.enforce_bip94 || !should_diff_adjust(last_commited_header.blockheight+1) || timestamp >= prev_block_timestamp.saturating_sub(MAX_TIMEWARP),
+        RelayErrorCode::ErrTimewarp
     );
 
+    //Verify timestamp is no more than max_future_blocktime in the future,
+    // this is skipped on regtest, as local nodes are commonly run with mocked time (setmocktime)
+    if let Some(max_future_blocktime) = PARAMS.max_future_blocktime {
+        let current_timestamp = now_ts()?;
+        require!(
+            timestamp < current_timestamp+max_future_blocktime,
+            RelayErrorCode::ErrTimestampTooHigh
+        );
+    }
+
     //Set commited header's variables
     last_commited_header.header = *header;
     last_commited_header.blockheight += 1;
@@ -338,14 +240,113 @@ pub fn verify_header(header: &BlockHeader, last_commited_header: &mut CommittedB
         last_commited_header.prev_block_timestamps[i-1] = last_commited_header.prev_block_timestamps[i];
     }
     last_commited_header.prev_block_timestamps[9] = prev_block_timestamp;
-    arrayutils::add_in_place(&mut last_commited_header.chain_work, get_difficulty(target));
+    #[cfg(any(feature = "bitcoin_testnet", feature = "bitcoin_testnet4"))]
+    {
+        if is_non_min_difficulty_block(last_commited_header.blockheight, header.nbits) {
+            last_commited_header.last_non_min_nbits = header.nbits;
+        }
+    }
+    last_commited_header.chain_work = (U256::from_be_bytes(last_commited_header.chain_work) + get_difficulty(target)).to_be_bytes();
+
+    Ok(())
+}
+
+//Decodes the blockheaders passed to the header submission instructions, computing their blockhashes,
+// prev_header is the blockheader right before the first one
+pub fn decode_headers(data: &HeadersData, prev_header: &HashedBlockHeader) -> Result<Vec<HashedBlockHeader>> {
+    //Signet blockheaders have to be submitted along with their block solution, which is only accepted on signet
+    require!(
+        matches!(data, HeadersData::Signet(_)) == PARAMS.signet_challenge.is_some(),
+        RelayErrorCode::UnsupportedNetwork
+    );
+
+    let headers: Vec<HashedBlockHeader> = match data {
+        HeadersData::Borsh(headers) => headers.iter().map(|header| HashedBlockHeader::new(*header)).collect::<Result<_>>()?,
+        HeadersData::Raw(data) => parse_raw_headers(data)?,
+        HeadersData::Compact(data) => decompress_headers(data, prev_header)?,
+        HeadersData::Signet(data) => signet::verify_block_solutions(data)?
+            .into_iter()
+            .map(HashedBlockHeader::new)
+            .collect::<Result<_>>()?
+    };
+
+    require!(
+        !headers.is_empty(),
+        RelayErrorCode::NoHeaders
+    );
 
-    Ok(last_block_hash)
+    Ok(headers)
+}
+
+//Parses raw bitcoin blockheaders (80 bytes each, as returned by bitcoind's getblockheader with verbose=false),
+// concatenated together, the blockhashes are computed straight from the raw blockheaders
+pub fn parse_raw_headers(data: &[u8]) -> Result<Vec<HashedBlockHeader>> {
+    require!(
+        !data.is_empty() && data.len().is_multiple_of(BLOCK_HEADER_SIZE),
+        RelayErrorCode::NoHeaders
+    );
+
+    data.chunks_exact(BLOCK_HEADER_SIZE)
+        .map(HashedBlockHeader::from_raw)
+        .collect()
+}
+
+//Reconstructs full blockheaders from the compact blockheaders, prev_header is the blockheader right before the first one,
+// the reconstructed blockheaders still have to be verified, omitted fields are only filled from the previous blockheader
+//Every reconstructed blockheader is hashed only once, its blockhash is then used as the next blockheader's previous
+// blockhash, so the reconstructed blockheaders always link to the previous one by construction
+pub fn decompress_headers(data: &[CompactBlockHeader], prev_header: &HashedBlockHeader) -> Result<Vec<HashedBlockHeader>> {
+    require!(
+        !data.is_empty(),
+        RelayErrorCode::NoHeaders
+    );
+
+    let mut headers: Vec<HashedBlockHeader> = Vec::with_capacity(data.len());
+    let mut last_header = *prev_header;
+    for compact_header in data.iter() {
+        last_header = HashedBlockHeader::new(compact_header.to_block_header(last_header.block_hash, last_header.header.nbits))?;
+        headers.push(last_header);
+    }
+
+    Ok(headers)
+}
+
+//Verifies a chain of raw bitcoin blockheaders (80 bytes each, ordered from the oldest), the newest blockheader
+// has to have the reversed_blockhash, and every blockheader has to link to the previous one by its reversed_prev_blockhash,
+// checks PoW of every blockheader, returns the oldest blockheader in the chain
+pub fn verify_header_chain(data: &[u8], reversed_blockhash: [u8; 32]) -> Result<BlockHeader> {
+    require!(
+        !data.is_empty() && data.len().is_multiple_of(BLOCK_HEADER_SIZE),
+        RelayErrorCode::NoHeaders
+    );
+
+    let mut expected_block_hash = reversed_blockhash;
+    let mut header: Option<BlockHeader> = None;
+    for raw_header in data.chunks_exact(BLOCK_HEADER_SIZE).rev() {
+        let HashedBlockHeader { header: current_header, block_hash } = HashedBlockHeader::from_raw(raw_header)?;
+
+        //Blockhash matches the reversed_prev_blockhash of the next block
+        require!(
+            block_hash == expected_block_hash,
+            RelayErrorCode::PrevBlock
+        );
+
+        //Check block's PoW, it's hash (little endian) has to be less than the target
+        require!(
+            U256::from_le_bytes(block_hash) <= nbits_to_target(current_header.nbits),
+            RelayErrorCode::ErrPowToolow
+        );
+
+        expected_block_hash = current_header.reversed_prev_blockhash;
+        header = Some(current_header);
+    }
+
+    Ok(header.unwrap())
 }
 
 //Calculates merkle root based on the transaction id and merkle proof,
 // reversed_ prefix is used because bitcoin uses little endian encoding
-pub fn compute_merkle(reversed_txid: &[u8; 32], _tx_index: u32, reversed_merkle_proof: &Vec<[u8; 32]>) -> [u8; 32] {
+pub fn compute_merkle(reversed_txid: &[u8; 32], _tx_index: u32, reversed_merkle_proof: &[[u8; 32]]) -> [u8; 32] {
     if reversed_merkle_proof.is_empty() {
         return *reversed_txid;
     }
@@ -370,3 +371,66 @@ pub fn compute_merkle(reversed_txid: &[u8; 32], _tx_index: u32, reversed_merkle_
 
     current_hash
 }
+
+//Verifies that the coinbase transaction is included in the block with the specified merkle root,
+// returns the parsed coinbase transaction
+pub fn verify_coinbase<'a>(coinbase: &'a CoinbaseProof, merkle_root: &[u8; 32]) -> Result<btctx::BitcoinTransaction<'a>> {
+    //Security against spoofing bitcoin txs as merkle tree nodes
+    // https://blog.rsk.co/ru/noticia/the-design-of-bitcoin-merkle-trees-reduces-the-security-of-spv-clients/
+    require!(
+        coinbase.transaction.len()!=64,
+        RelayErrorCode::InvalidCoinbase
+    );
+
+    let opt_tx = btctx::parse_transaction(&coinbase.transaction);
+    require!(
+        opt_tx.is_some(),
+        RelayErrorCode::InvalidCoinbase
+    );
+    let tx = opt_tx.unwrap();
+
+    //Coinbase transaction has a single input spending an empty UTXO
+    require!(
+        tx.inputs.len()==1 && tx.inputs[0].reversed_prev_txid==[0; 32] && tx.inputs[0].prev_vout==0xFFFFFFFF,
+        RelayErrorCode::InvalidCoinbase
+    );
+
+    //Coinbase transaction is always the first transaction in the block
+    let reversed_txid = btctx::get_reversed_txid(&coinbase.transaction);
+    require!(
+        compute_merkle(&reversed_txid, 0, &coinbase.reversed_merkle_proof) == *merkle_root,
+        RelayErrorCode::InvalidCoinbase
+    );
+
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nbits_round_trip() {
+        //nBits of real blocks: mainnet genesis, first mainnet retarget (block 32256), mainnet blocks 100000 & 125552,
+        // signet/testnet4 powLimit and regtest powLimit
+        for nbits in [0x1d00ffff, 0x1d00d86a, 0x1b04864c, 0x1a44b9f2, 0x1e0377ae, 0x207fffff] {
+            assert_eq!(target_to_nbits(nbits_to_target(nbits)), nbits);
+        }
+
+        assert_eq!(nbits_to_target(0x1d00ffff), U256::from_u64(0xffff) << 208);
+        assert_eq!(nbits_to_target(0x1b04864c), U256::from_u64(0x04864c) << 192);
+    }
+
+    #[test]
+    fn nbits_sign_bit() {
+        //Mantissa with the highest bit set would be negative, so it's shifted by a byte
+        assert_eq!(target_to_nbits(U256::from_u64(0x800000)), 0x04008000);
+        assert_eq!(target_to_nbits(U256::from_u64(0x80) << 200), 0x1b008000);
+        assert_eq!(nbits_to_target(0x04008000), U256::from_u64(0x800000));
+
+        //Small targets fitting in the mantissa
+        assert_eq!(target_to_nbits(U256::from_u64(0x12)), 0x01120000);
+        assert_eq!(nbits_to_target(0x01120000), U256::from_u64(0x12));
+        assert_eq!(target_to_nbits(U256::ZERO), 0);
+    }
+}
@@ -232,7 +232,8 @@ describe("btc-relay", () => {
           0x63a080a4,
           0x63a080a4,
           0x63a080a4
-        ]
+        ],
+        PRUNING_FACTOR
       )
       .accounts({
         signer: signer.publicKey,
@@ -337,8 +338,9 @@ describe("btc-relay", () => {
     
     const tx = await programPaidBy(signer).methods
       .submitBlockHeaders(
-        [header2],
-        commitedHeader
+        { borsh: { 0: [header2] } },
+        commitedHeader,
+        true
       )
       .accounts({
         signer: signer.publicKey,
@@ -65,4 +65,11 @@ pub enum SwapErrorCode {
     InvalidSwapDataPayIn,
     #[msg("Invalid swap data: nonce")]
     InvalidSwapDataNonce,
+
+    #[msg("Invalid program id for liveness verification.")]
+    InvalidLivenessVerifyProgramId,
+    #[msg("Invalid instruction for liveness verification.")]
+    InvalidLivenessVerifyIx,
+    #[msg("Invalid max tip age for liveness verification.")]
+    InvalidLivenessVerifyAge,
 }
\ No newline at end of file
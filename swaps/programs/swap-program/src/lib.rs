@@ -36,6 +36,8 @@ const AUTHORITY_SEED: &[u8] = b"authority";
 const USER_DATA_SEED: &[u8] = b"uservault";
 const BLOCKHEIGHT_EXPIRY_THRESHOLD: u64 = 1000000000; //If expiry is < BLOCKHEIGHT_EXPIRY_THRESHOLD it is considered as expressed in blockheight instead of timestamp
 
+//Minimum pruning factor (ring buffer length) enforced by the btc relay program (MIN_PRUNING_FACTOR), every btc relay
+// deployment keeps at least this many blocks in its ring buffer, regardless of the pruning factor it was initialized with
 const BTCRELAY_PRUNING_FACTOR: u16 = 250;
 const BTCRELAY_SAFETY_BUFFER: u16 = 50;
 const MAX_CONFIRMATIONS: u16 = BTCRELAY_PRUNING_FACTOR - BTCRELAY_SAFETY_BUFFER;
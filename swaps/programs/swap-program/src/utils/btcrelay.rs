@@ -16,6 +16,36 @@ const TX_VERIFY_IX_PREFIX: [u8; 8] = [
     0x07,
     0x58
 ];
+const TX_VERIFY_HISTORIC_IX_PREFIX: [u8; 8] = [
+    0x66,
+    0xfd,
+    0x22,
+    0x3d,
+    0x35,
+    0xf4,
+    0xe1,
+    0x7f
+];
+const TX_VERIFY_LINKED_IX_PREFIX: [u8; 8] = [
+    0x7a,
+    0x23,
+    0x15,
+    0x96,
+    0xd2,
+    0x02,
+    0xfb,
+    0x2d
+];
+const TX_VERIFY_WITH_COINBASE_IX_PREFIX: [u8; 8] = [
+    0x03,
+    0x20,
+    0x0e,
+    0xf2,
+    0x0f,
+    0x6d,
+    0x1d,
+    0x00
+];
 const BLOCKHEIGHT_IX_PREFIX: [u8; 8] = [
     0xd3,
     0xdc,
@@ -26,8 +56,20 @@ const BLOCKHEIGHT_IX_PREFIX: [u8; 8] = [
     0xec,
     0xea
 ];
+const LIVENESS_IX_PREFIX: [u8; 8] = [
+    0x43,
+    0x60,
+    0xfc,
+    0x02,
+    0x0b,
+    0x69,
+    0x65,
+    0x4e
+];
 
-// Checks if current transaction includes an instruction calling verify_transaction on btcrelay program
+// Checks if current transaction includes an instruction calling verify_transaction
+//  (or verify_transaction_historic/verify_transaction_linked, for blocks already pruned from the relay's ring buffer,
+//  or verify_transaction_with_coinbase, which additionally checks the merkle proof depth) on btcrelay program
 // Returns 0 on success, and positive integer on failure
 pub fn verify_tx_ix(ix: &Instruction, reversed_tx_id: &[u8; 32], confirmations: u32) -> Result<()> {
     let btc_relay_id: Pubkey = Pubkey::from_str(BTC_RELAY_ID_BASE58).unwrap();
@@ -40,12 +82,11 @@ pub fn verify_tx_ix(ix: &Instruction, reversed_tx_id: &[u8; 32], confirmations:
     check_tx_data(&ix.data, reversed_tx_id, confirmations)
 }
 
-// Verify serialized BtcRelay instruction data
+// Verify serialized BtcRelay instruction data, all the transaction verification instructions
+//  start with the same reversed_tx_id & confirmations arguments
 pub fn check_tx_data(data: &[u8], reversed_tx_id: &[u8; 32], confirmations: u32) -> Result<()> {
-    for i in 0..8 {
-        if data[i] != TX_VERIFY_IX_PREFIX[i] {
-            return Err(anchor_lang::error!(SwapErrorCode::InvalidTxVerifyIx));
-        }
+    if data[0..8] != TX_VERIFY_IX_PREFIX && data[0..8] != TX_VERIFY_HISTORIC_IX_PREFIX && data[0..8] != TX_VERIFY_LINKED_IX_PREFIX && data[0..8] != TX_VERIFY_WITH_COINBASE_IX_PREFIX {
+        return Err(anchor_lang::error!(SwapErrorCode::InvalidTxVerifyIx));
     }
     for i in 8..40 {
         if data[i] != reversed_tx_id[i-8] {
@@ -102,3 +143,116 @@ pub fn check_blockheight_data(data: &[u8], blockheight: u32, operation: u32) ->
 
     Ok(())
 }
+
+// Checks that the relay's tip is recent, by checking if the tx includes an instruction calling verify_liveness on btcrelay program
+//  with at most max_tip_age seconds old tip
+#[allow(dead_code)] //Not yet required by any of the swap instructions
+pub fn verify_liveness_ix(ix: &Instruction, max_tip_age: u32) -> Result<()> {
+    let btc_relay_id: Pubkey = Pubkey::from_str(BTC_RELAY_ID_BASE58).unwrap();
+
+    if  ix.program_id       != btc_relay_id
+    {
+        return Err(anchor_lang::error!(SwapErrorCode::InvalidLivenessVerifyProgramId));
+    }
+
+    check_liveness_data(&ix.data, max_tip_age)
+}
+
+// Verify serialized BtcRelay instruction data, the verify_liveness instruction mustn't allow an older tip than max_tip_age
+pub fn check_liveness_data(data: &[u8], max_tip_age: u32) -> Result<()> {
+    if data.len() < 12 || data[0..8] != LIVENESS_IX_PREFIX {
+        return Err(anchor_lang::error!(SwapErrorCode::InvalidLivenessVerifyIx));
+    }
+
+    let ix_max_tip_age = u32::from_le_bytes(data[8..12].try_into().unwrap());
+    if ix_max_tip_age > max_tip_age {
+        return Err(anchor_lang::error!(SwapErrorCode::InvalidLivenessVerifyAge));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::hash;
+
+    //Anchor instruction discriminator of the btc relay's instruction
+    fn discriminator(name: &str) -> [u8; 8] {
+        hash::hash(format!("global:{}", name).as_bytes()).to_bytes()[0..8].try_into().unwrap()
+    }
+
+    #[test]
+    fn prefixes_match_discriminators() {
+        assert_eq!(TX_VERIFY_IX_PREFIX, discriminator("verify_transaction"));
+        assert_eq!(TX_VERIFY_HISTORIC_IX_PREFIX, discriminator("verify_transaction_historic"));
+        assert_eq!(TX_VERIFY_LINKED_IX_PREFIX, discriminator("verify_transaction_linked"));
+        assert_eq!(TX_VERIFY_WITH_COINBASE_IX_PREFIX, discriminator("verify_transaction_with_coinbase"));
+        assert_eq!(BLOCKHEIGHT_IX_PREFIX, discriminator("block_height"));
+        assert_eq!(LIVENESS_IX_PREFIX, discriminator("verify_liveness"));
+    }
+
+    //verify_liveness instruction data, max_tip_age followed by the (here zeroed) commited header
+    fn liveness_data(max_tip_age: u32) -> Vec<u8> {
+        let mut data = discriminator("verify_liveness").to_vec();
+        data.extend_from_slice(&max_tip_age.to_le_bytes());
+        data.extend_from_slice(&[0u8; 200]);
+        data
+    }
+
+    #[test]
+    fn check_liveness() {
+        check_liveness_data(&liveness_data(3600), 3600).unwrap();
+        check_liveness_data(&liveness_data(600), 3600).unwrap();
+
+        //Tip allowed to be older than required
+        assert_eq!(
+            check_liveness_data(&liveness_data(3601), 3600).unwrap_err(),
+            anchor_lang::error!(SwapErrorCode::InvalidLivenessVerifyAge)
+        );
+
+        //Different instruction
+        let mut data = liveness_data(600);
+        data[0..8].copy_from_slice(&discriminator("block_height"));
+        assert_eq!(
+            check_liveness_data(&data, 3600).unwrap_err(),
+            anchor_lang::error!(SwapErrorCode::InvalidLivenessVerifyIx)
+        );
+
+        //Truncated data
+        assert_eq!(
+            check_liveness_data(&liveness_data(600)[..11], 3600).unwrap_err(),
+            anchor_lang::error!(SwapErrorCode::InvalidLivenessVerifyIx)
+        );
+    }
+
+    //Transaction verification instruction data, reversed_tx_id & confirmations followed by the (here zeroed) rest of the arguments
+    fn tx_data(name: &str, reversed_tx_id: &[u8; 32], confirmations: u32) -> Vec<u8> {
+        let mut data = discriminator(name).to_vec();
+        data.extend_from_slice(reversed_tx_id);
+        data.extend_from_slice(&confirmations.to_le_bytes());
+        data.extend_from_slice(&[0u8; 200]);
+        data
+    }
+
+    #[test]
+    fn check_tx() {
+        let reversed_tx_id = [0x11; 32];
+        for name in ["verify_transaction", "verify_transaction_historic", "verify_transaction_linked", "verify_transaction_with_coinbase"] {
+            check_tx_data(&tx_data(name, &reversed_tx_id, 3), &reversed_tx_id, 3).unwrap();
+        }
+
+        assert_eq!(
+            check_tx_data(&tx_data("verify_transaction_with_coinbase", &[0x22; 32], 3), &reversed_tx_id, 3).unwrap_err(),
+            anchor_lang::error!(SwapErrorCode::InvalidTxVerifyTxid)
+        );
+        assert_eq!(
+            check_tx_data(&tx_data("verify_transaction_with_coinbase", &reversed_tx_id, 2), &reversed_tx_id, 3).unwrap_err(),
+            anchor_lang::error!(SwapErrorCode::InvalidTxVerifyConfirmations)
+        );
+        assert_eq!(
+            check_tx_data(&tx_data("verify_witness_transaction", &reversed_tx_id, 3), &reversed_tx_id, 3).unwrap_err(),
+            anchor_lang::error!(SwapErrorCode::InvalidTxVerifyIx)
+        );
+    }
+}